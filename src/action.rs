@@ -0,0 +1,60 @@
+// action.rs
+
+// things that keys, buttons, gestures and socket commands can do
+
+use log::warn;
+use std::process::Command;
+use std::thread;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Quit,
+    Reload,
+    NextPage,
+    PreviousPage,
+    FirstPage,
+    Fullscreen,
+    Dim,
+    Dismiss,
+    Run(String),
+}
+
+impl Action {
+    /// Parse an action name as used in the bindings table and on the socket;
+    /// "run <command>" takes the rest of the text as a shell command
+    pub fn parse(text: &str) -> Option<Action> {
+        let text = text.trim();
+        match text {
+            "quit" => Some(Action::Quit),
+            "reload" => Some(Action::Reload),
+            "next_page" => Some(Action::NextPage),
+            "previous_page" => Some(Action::PreviousPage),
+            "first_page" => Some(Action::FirstPage),
+            "fullscreen" => Some(Action::Fullscreen),
+            "dim" => Some(Action::Dim),
+            "dismiss" => Some(Action::Dismiss),
+            _ => match text.strip_prefix("run ") {
+                Some(command) if !command.trim().is_empty() => {
+                    Some(Action::Run(command.trim().to_string()))
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Run a shell command in the background with some extra environment;
+/// a thread reaps the child when it exits
+pub fn spawn(command: &str, vars: &[(&str, &str)]) {
+    let mut c = Command::new("/bin/sh");
+    c.arg("-c").arg(command);
+    for (name, value) in vars {
+        c.env(name, value);
+    }
+    match c.spawn() {
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(e) => warn!("command failed: {}: {}", command, e),
+    }
+}
@@ -0,0 +1,102 @@
+// alarm.rs
+
+use chrono::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alarm {
+    pub hour: u32,
+    pub minute: u32,
+    pub days: [bool; 7], // indexed from Sunday
+    pub label: String,
+}
+
+impl Alarm {
+    /// An alarm for every day at a time given as "HH:MM"
+    pub fn new(time: &str, label: &str) -> Option<Alarm> {
+        let (hour, minute) = parse_time(time)?;
+        Some(Alarm {
+            hour,
+            minute,
+            days: [true; 7],
+            label: label.to_string(),
+        })
+    }
+
+    /// true if the alarm should go off during the minute containing |dt|
+    pub fn is_due(&self, dt: &DateTime<Local>) -> bool {
+        self.days[dt.weekday().num_days_from_sunday() as usize]
+            && self.hour == dt.hour()
+            && self.minute == dt.minute()
+    }
+}
+
+// "HH:MM" as (hour, minute)
+pub fn parse_time(time: &str) -> Option<(u32, u32)> {
+    let mut parts = time.trim().splitn(2, ':');
+    let hour = parts.next()?.parse::<u32>().ok()?;
+    let minute = parts.next()?.parse::<u32>().ok()?;
+    if hour < 24 && minute < 60 {
+        Some((hour, minute))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 7 January 2024 is a Sunday
+    fn at(day: u32, hour: u32, minute: u32, second: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2024, 1, day, hour, minute, second)
+            .unwrap()
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("07:00"), Some((7, 0)));
+        assert_eq!(parse_time("7:05"), Some((7, 5)));
+        assert_eq!(parse_time(" 23:59\n"), Some((23, 59)));
+        assert_eq!(parse_time("00:00"), Some((0, 0)));
+    }
+
+    #[test]
+    fn malformed_times() {
+        assert_eq!(parse_time("25:00"), None);
+        assert_eq!(parse_time("24:00"), None);
+        assert_eq!(parse_time("12:60"), None);
+        assert_eq!(parse_time("7:5x"), None);
+        assert_eq!(parse_time("7"), None);
+        assert_eq!(parse_time("7:"), None);
+        assert_eq!(parse_time(":30"), None);
+        assert_eq!(parse_time("7:30:00"), None);
+        assert_eq!(parse_time("-1:30"), None);
+        assert_eq!(parse_time(""), None);
+        assert!(Alarm::new("25:00", "late").is_none());
+    }
+
+    #[test]
+    fn due_for_the_whole_minute() {
+        let alarm = Alarm::new("07:00", "wake").unwrap();
+        assert!(alarm.is_due(&at(8, 7, 0, 0)));
+        assert!(alarm.is_due(&at(8, 7, 0, 59)));
+        assert!(!alarm.is_due(&at(8, 6, 59, 59)));
+        assert!(!alarm.is_due(&at(8, 7, 1, 0)));
+        assert!(!alarm.is_due(&at(8, 19, 0, 0)));
+    }
+
+    #[test]
+    fn weekdays() {
+        // Monday to Friday
+        let mut alarm = Alarm::new("07:00", "work").unwrap();
+        alarm.days = [false, true, true, true, true, true, false];
+        assert!(!alarm.is_due(&at(7, 7, 0, 0))); // Sunday
+        assert!(alarm.is_due(&at(8, 7, 0, 0))); // Monday
+        assert!(alarm.is_due(&at(12, 7, 0, 0))); // Friday
+        assert!(!alarm.is_due(&at(13, 7, 0, 0))); // Saturday
+
+        let every_day = Alarm::new("07:00", "").unwrap();
+        assert!((7..=13).all(|day| every_day.is_due(&at(day, 7, 0, 0))));
+    }
+}
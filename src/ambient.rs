@@ -0,0 +1,83 @@
+// ambient.rs
+
+// smooth ambient light readings and turn them into a backlight level
+// and a choice between the normal and dim themes
+
+use crate::configure;
+use std::fs;
+
+// leave the dim theme only once it is this much brighter than the
+// threshold, so a reading hovering around it does not flicker
+const HYSTERESIS: f64 = 1.25;
+
+pub struct Ambient {
+    config: configure::Ambient,
+    lux: Option<f64>, // smoothed
+    dark: bool,
+}
+
+impl Ambient {
+    pub fn new(config: configure::Ambient) -> Ambient {
+        Ambient {
+            config,
+            lux: None,
+            dark: false,
+        }
+    }
+
+    /// Take a new reading: from the sensor file if configured,
+    /// otherwise the last value sent to the socket
+    pub fn update(&mut self, socket: Option<f64>) {
+        let reading = match &self.config.sensor {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(text) => text.trim().parse::<f64>().ok(),
+                Err(_) => None,
+            },
+            None => socket,
+        };
+        let reading = match reading {
+            Some(r) => r * self.config.scale,
+            None => return,
+        };
+
+        let lux = match self.lux {
+            Some(lux) => lux + self.config.smoothing * (reading - lux),
+            None => reading,
+        };
+        self.lux = Some(lux);
+
+        if let Some(threshold) = self.config.dim_below {
+            if lux < threshold {
+                self.dark = true;
+            } else if lux >= threshold * HYSTERESIS {
+                self.dark = false;
+            }
+        }
+    }
+
+    /// true when it is dark enough for the dim theme
+    pub fn is_dark(&self) -> bool {
+        self.dark
+    }
+
+    /// Backlight percentage from the curve, interpolating between its
+    /// points; None without a reading or a curve
+    pub fn brightness(&self) -> Option<u32> {
+        let lux = self.lux?;
+        let curve = &self.config.curve;
+        let first = curve.first()?;
+        let last = curve.last()?;
+
+        if lux <= first.0 {
+            return Some(first.1);
+        }
+        if lux >= last.0 {
+            return Some(last.1);
+        }
+        let i = curve.iter().position(|p| p.0 > lux)?;
+        let (x0, y0) = curve[i - 1];
+        let (x1, y1) = curve[i];
+        let y = y0 as f64 + (y1 as f64 - y0 as f64) * (lux - x0) / (x1 - x0);
+        Some(y.round() as u32)
+    }
+}
@@ -0,0 +1,105 @@
+// backlight.rs
+
+// set the panel brightness through a sysfs backlight directory such
+// as /sys/class/backlight/rpi_backlight, which holds max_brightness
+// and a writable brightness file
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+pub struct Backlight {
+    brightness: PathBuf,
+    max: u32,
+    level: Option<u32>, // last percentage written
+}
+
+impl Backlight {
+    pub fn new(directory: &str) -> io::Result<Backlight> {
+        let directory = PathBuf::from(directory);
+        let max = fs::read_to_string(directory.join("max_brightness"))?
+            .trim()
+            .parse::<u32>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Backlight {
+            brightness: directory.join("brightness"),
+            max,
+            level: None,
+        })
+    }
+
+    /// Set brightness as a percentage of the maximum, only writing when
+    /// it changes
+    pub fn set(&mut self, percent: u32) -> io::Result<()> {
+        let percent = percent.min(100);
+        if self.level == Some(percent) {
+            return Ok(());
+        }
+        // remember even a failed level so errors are not repeated every second
+        self.level = Some(percent);
+
+        let value = (self.max as u64 * percent as u64 + 50) / 100;
+        fs::write(&self.brightness, value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::Path;
+    use std::process;
+
+    // a fresh backlight directory holding |max| as max_brightness
+    fn directory(name: &str, max: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("rusty-clock-{}-{}", name, process::id()));
+        fs::remove_dir_all(&directory).ok();
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("max_brightness"), max).unwrap();
+        directory
+    }
+
+    fn brightness(directory: &Path) -> String {
+        fs::read_to_string(directory.join("brightness")).unwrap()
+    }
+
+    #[test]
+    fn writes_rounded_value() {
+        let directory = directory("rounded", "255\n");
+        let mut backlight = Backlight::new(directory.to_str().unwrap()).unwrap();
+
+        backlight.set(50).unwrap();
+        assert_eq!(brightness(&directory), "128"); // 127.5
+        backlight.set(10).unwrap();
+        assert_eq!(brightness(&directory), "26"); // 25.5
+        backlight.set(33).unwrap();
+        assert_eq!(brightness(&directory), "84"); // 84.15
+        backlight.set(150).unwrap();
+        assert_eq!(brightness(&directory), "255");
+
+        fs::remove_dir_all(&directory).ok();
+    }
+
+    #[test]
+    fn same_level_not_rewritten() {
+        let directory = directory("same", "100");
+        let mut backlight = Backlight::new(directory.to_str().unwrap()).unwrap();
+
+        backlight.set(40).unwrap();
+        assert_eq!(brightness(&directory), "40");
+        fs::write(directory.join("brightness"), "untouched").unwrap();
+        backlight.set(40).unwrap();
+        assert_eq!(brightness(&directory), "untouched");
+
+        fs::remove_dir_all(&directory).ok();
+    }
+
+    #[test]
+    fn invalid_max_brightness() {
+        let directory = directory("invalid", "bright");
+        let e = Backlight::new(directory.to_str().unwrap()).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(&directory).ok();
+    }
+}
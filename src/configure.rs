@@ -1,6 +1,12 @@
 // configure.rs
 
-use rlua::{Lua, Result, Table, Value};
+use crate::action::Action;
+use crate::alarm::{self, Alarm};
+use crate::gesture::{self, Gesture};
+use chrono::format::{Item, StrftimeItems};
+use chrono_tz::Tz;
+use log::{info, trace};
+use rlua::{Lua, Result, Table, ToLua, Value};
 use std::collections::HashMap;
 use std::fmt;
 use std::fs::File;
@@ -13,6 +19,7 @@ pub enum ConfigError {
     NilValueError(String),
     TypeError(String),
     NotSevenDaysError(String),
+    InvalidValueError(String),
     LuaError(rlua::Error),
 }
 
@@ -24,6 +31,9 @@ impl fmt::Display for ConfigError {
             ConfigError::NotSevenDaysError(ref message) => {
                 write!(fmt, "require seven day entries in: {}", message)
             }
+            ConfigError::InvalidValueError(ref message) => {
+                write!(fmt, "invalid value for: {}", message)
+            }
             ConfigError::LuaError(ref message) => write!(fmt, "Lua error: {}", message),
         }
     }
@@ -35,6 +45,7 @@ impl std::error::Error for ConfigError {
             ConfigError::NilValueError(_) => "nil value",
             ConfigError::TypeError(_) => "incorrect type",
             ConfigError::NotSevenDaysError(_) => "require seven day entries",
+            ConfigError::InvalidValueError(_) => "invalid value",
             ConfigError::LuaError(_) => "Lua eror",
         }
     }
@@ -48,9 +59,10 @@ impl From<rlua::Error> for ConfigError {
     }
 }
 
-pub type StrMap = HashMap<String, String>;
+// each value is a string or a list of them in order of preference
+pub type ListMap = HashMap<String, Vec<String>>;
 pub type PointMap = HashMap<String, Point>;
-pub type ThemeMap = HashMap<String, StrMap>;
+pub type ThemeMap = HashMap<String, ListMap>;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Point {
@@ -58,6 +70,143 @@ pub struct Point {
     pub y: i32,
 }
 
+// the span of time a progress widget fills up over
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Period {
+    Minute,
+    Hour,
+    Day,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ProgressStyle {
+    Bar,
+    Ring,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Progress {
+    pub period: Period,
+    pub style: ProgressStyle,
+    pub width: i32,
+    pub height: i32,
+    pub thickness: i32,
+}
+
+// how the display moves about to spread wear on the panel
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ShiftMode {
+    Orbit,
+    Random,
+}
+
+// every |interval| minutes everything moves by up to |range| pixels
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Shift {
+    pub interval: i32,
+    pub range: i32,
+    pub mode: ShiftMode,
+}
+
+// an ambient light reading, from a sysfs file or the socket, mapped
+// through |curve| (pairs of lux and backlight percent, rising) and
+// switching to the dim theme below |dim_below| lux
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ambient {
+    pub sensor: Option<String>,
+    pub scale: f64,     // raw reading to lux
+    pub smoothing: f64, // weight of each new reading, 1.0 for none
+    pub curve: Vec<(f64, u32)>,
+    pub dim_below: Option<f64>,
+}
+
+// what the display does during the night
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NightMode {
+    Minimal, // just the time in the night theme
+    Blank,
+}
+
+// the night runs from |start| to |stop| as (hour, minute), and a touch
+// wakes the display for |wake| seconds
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Night {
+    pub start: (u32, u32),
+    pub stop: (u32, u32),
+    pub mode: NightMode,
+    pub wake: i32,
+}
+
+// the panel's power: |keep_on| stops the X screensaver and DPMS
+// blanking it, and between |off| and |on| the display is switched off
+// except for |wake| seconds after a touch
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Power {
+    pub keep_on: bool,
+    pub off: Option<(u32, u32)>,
+    pub on: Option<(u32, u32)>,
+    pub wake: i32,
+}
+
+// without an M.power table the display is still kept on, as the
+// session script used to do with xset
+impl Default for Power {
+    fn default() -> Power {
+        Power {
+            keep_on: true,
+            off: None,
+            on: None,
+            wake: 30,
+        }
+    }
+}
+
+// zero column or row sizes are computed from the font
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Calendar {
+    pub first_day: u32,
+    pub column: i32,
+    pub row: i32,
+}
+
+// an extra clock showing the time in another time zone, its name
+// selects the entries in the coordinates and fonts tables
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub name: String,
+    pub zone: Tz,
+    pub label: String,
+    pub format: String,
+}
+
+// what keys (by keysym name), buttons and gestures do; None
+// removes a default binding
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    pub keys: HashMap<String, Option<Action>>,
+    pub buttons: HashMap<u32, Option<Action>>,
+    pub gestures: HashMap<Gesture, Option<Action>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        let mut keys = HashMap::new();
+        keys.insert("Escape".to_string(), Some(Action::Quit));
+
+        let mut gestures = HashMap::new();
+        gestures.insert(Gesture::Tap, Some(Action::Dismiss));
+        gestures.insert(Gesture::LongPress, Some(Action::FirstPage));
+        gestures.insert(Gesture::SwipeLeft, Some(Action::NextPage));
+        gestures.insert(Gesture::SwipeRight, Some(Action::PreviousPage));
+
+        Bindings {
+            keys,
+            buttons: HashMap::new(),
+            gestures,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Configuration {
     pub socket: String,
@@ -65,26 +214,38 @@ pub struct Configuration {
     pub height: i32,
     pub days: [String; 7],
     pub coordinates: PointMap,
-    pub fonts: StrMap,
+    pub fonts: ListMap,
     pub themes: ThemeMap,
+    pub progress: Option<Progress>,
+    pub calendar: Option<Calendar>,
+    pub zones: Vec<Zone>,
+    pub alarms: Vec<Alarm>,
+    pub alarm_command: Option<String>,
+    pub pages: Vec<Vec<String>>,
+    pub idle_timeout: i32,
+    pub hide_cursor: Option<bool>, // None: hide when fullscreen
+    pub cursor_timeout: i32,       // seconds shown after motion, 0 never
+    pub bindings: Bindings,
+    pub output: Option<String>, // XRandR output name
+    pub shift: Option<Shift>,
+    pub backlight: Option<String>, // sysfs backlight directory
+    pub ambient: Option<Ambient>,
+    pub night: Option<Night>,
+    pub power: Power,
 }
 
 //type MyResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 type MyResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-pub fn read(filename: &std::path::PathBuf, debug: bool) -> MyResult<Configuration> {
-    if debug {
-        println!("configuration file: {:?}", filename);
-    }
+pub fn read(filename: &std::path::PathBuf) -> MyResult<Configuration> {
+    info!("configuration file: {:?}", filename);
 
     let file = File::open(filename)?;
     let mut buf_reader = BufReader::new(file);
     let mut contents = String::new();
     buf_reader.read_to_string(&mut contents)?;
 
-    if debug {
-        println!("configuration text: {}", contents);
-    }
+    trace!("configuration text: {}", contents);
 
     let lua = Lua::new();
     lua.context(|lua| {
@@ -171,6 +332,150 @@ pub fn read(filename: &std::path::PathBuf, debug: bool) -> MyResult<Configuratio
             Err(e) => Err(ConfigError::LuaError(e)),
         }?;
 
+        let progress = match config.get("progress") {
+            Ok(v) => match v {
+                Value::Table(t) => Ok(Some(make_progress(t)?)),
+                Value::Nil => Ok(None),
+                _ => Err(ConfigError::TypeError("progress".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let shift = match config.get("shift") {
+            Ok(v) => match v {
+                Value::Table(t) => Ok(Some(make_shift(t)?)),
+                Value::Nil => Ok(None),
+                _ => Err(ConfigError::TypeError("shift".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let calendar = match config.get("calendar") {
+            Ok(v) => match v {
+                Value::Table(t) => Ok(Some(make_calendar(t)?)),
+                Value::Nil => Ok(None),
+                _ => Err(ConfigError::TypeError("calendar".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let zones = match config.get("zones") {
+            Ok(v) => match v {
+                Value::Table(t) => make_zones(t),
+                Value::Nil => Ok(Vec::new()),
+                _ => Err(ConfigError::TypeError("zones".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let alarms = match config.get("alarms") {
+            Ok(v) => match v {
+                Value::Table(t) => make_alarms(t),
+                Value::Nil => Ok(Vec::new()),
+                _ => Err(ConfigError::TypeError("alarms".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let alarm_command = match config.get("alarm_command") {
+            Ok(v) => match v {
+                Value::String(s) => Ok(Some(s.to_str()?.to_string())),
+                Value::Nil => Ok(None),
+                _ => Err(ConfigError::TypeError("alarm_command".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let backlight = match config.get("backlight") {
+            Ok(v) => match v {
+                Value::String(s) => Ok(Some(s.to_str()?.to_string())),
+                Value::Nil => Ok(None),
+                _ => Err(ConfigError::TypeError("backlight".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let power = match config.get("power") {
+            Ok(v) => match v {
+                Value::Table(t) => Ok(make_power(t)?),
+                Value::Nil => Ok(Power::default()),
+                _ => Err(ConfigError::TypeError("power".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let night = match config.get("night") {
+            Ok(v) => match v {
+                Value::Table(t) => Ok(Some(make_night(t)?)),
+                Value::Nil => Ok(None),
+                _ => Err(ConfigError::TypeError("night".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let ambient = match config.get("ambient") {
+            Ok(v) => match v {
+                Value::Table(t) => Ok(Some(make_ambient(t)?)),
+                Value::Nil => Ok(None),
+                _ => Err(ConfigError::TypeError("ambient".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let output = match config.get("output") {
+            Ok(v) => match v {
+                Value::String(s) => Ok(Some(s.to_str()?.to_string())),
+                Value::Nil => Ok(None),
+                _ => Err(ConfigError::TypeError("output".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        // seconds without a touch before returning to the first page,
+        // zero to stay on the page
+        let idle_timeout = match config.get("idle_timeout") {
+            Ok(v) => match v {
+                Value::Integer(n) if n >= 0 => n as i32,
+                _ => 60,
+            },
+            Err(_) => 60,
+        };
+
+        let hide_cursor = match config.get("hide_cursor") {
+            Ok(v) => match v {
+                Value::Boolean(b) => Ok(Some(b)),
+                Value::Nil => Ok(None),
+                _ => Err(ConfigError::TypeError("hide_cursor".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let cursor_timeout = match config.get("cursor_timeout") {
+            Ok(v) => match v {
+                Value::Integer(n) if n >= 0 => n as i32,
+                _ => 0,
+            },
+            Err(_) => 0,
+        };
+
+        let pages = match config.get("pages") {
+            Ok(v) => match v {
+                Value::Table(t) => make_pages(t, &zones),
+                Value::Nil => Ok(Vec::new()),
+                _ => Err(ConfigError::TypeError("pages".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
+        let bindings = match config.get("bindings") {
+            Ok(v) => match v {
+                Value::Table(t) => make_bindings(t),
+                Value::Nil => Ok(Bindings::default()),
+                _ => Err(ConfigError::TypeError("bindings".to_string())),
+            },
+            Err(e) => Err(ConfigError::LuaError(e)),
+        }?;
+
         let cfg = Configuration {
             socket: socket,
             width: width,
@@ -179,23 +484,52 @@ pub fn read(filename: &std::path::PathBuf, debug: bool) -> MyResult<Configuratio
             fonts: make_map(fonts)?,
             coordinates: points_map(coordinates)?,
             themes: nested_map(themes)?,
+            progress,
+            calendar,
+            zones,
+            alarms,
+            alarm_command,
+            pages,
+            idle_timeout,
+            hide_cursor,
+            cursor_timeout,
+            bindings,
+            output,
+            shift,
+            backlight,
+            ambient,
+            night,
+            power,
         };
 
         Ok(cfg)
     })
 }
 
-fn make_map(item: Table) -> Result<HashMap<String, String>> {
-    let mut m: HashMap<String, String> = HashMap::new();
-    for pair in item.pairs::<String, String>() {
+fn make_map(item: Table) -> Result<ListMap> {
+    let mut m: ListMap = HashMap::new();
+    for pair in item.pairs::<String, Value>() {
         let (key, value) = pair?;
-        m.insert(key, value);
+        let values = match value {
+            Value::Table(t) => t.sequence_values::<String>().collect::<Result<Vec<_>>>()?,
+            Value::String(s) => vec![s.to_str()?.to_string()],
+            Value::Integer(n) => vec![n.to_string()],
+            Value::Number(n) => vec![n.to_string()],
+            v => {
+                return Err(rlua::Error::FromLuaConversionError {
+                    from: v.type_name(),
+                    to: "String",
+                    message: Some(format!("for {}", key)),
+                })
+            }
+        };
+        m.insert(key, values);
     }
     Ok(m)
 }
 
-fn nested_map(item: Table) -> Result<HashMap<String, StrMap>> {
-    let mut m: HashMap<String, StrMap> = HashMap::new();
+fn nested_map(item: Table) -> Result<HashMap<String, ListMap>> {
+    let mut m: HashMap<String, ListMap> = HashMap::new();
     for pair in item.pairs::<String, Table>() {
         let (key, value) = pair?;
         m.insert(key, make_map(value)?);
@@ -231,3 +565,352 @@ fn make_point(item: Table) -> Point {
 
     Point { x: x, y: y }
 }
+
+fn make_progress(item: Table) -> std::result::Result<Progress, ConfigError> {
+    let period = match item.get("period")? {
+        Value::Nil => Period::Minute,
+        Value::String(s) => match s.to_str()? {
+            "minute" => Period::Minute,
+            "hour" => Period::Hour,
+            "day" => Period::Day,
+            _ => {
+                return Err(ConfigError::InvalidValueError(
+                    "progress.period".to_string(),
+                ))
+            }
+        },
+        _ => return Err(ConfigError::TypeError("progress.period".to_string())),
+    };
+
+    let style = match item.get("style")? {
+        Value::Nil => ProgressStyle::Bar,
+        Value::String(s) => match s.to_str()? {
+            "bar" => ProgressStyle::Bar,
+            "ring" => ProgressStyle::Ring,
+            _ => return Err(ConfigError::InvalidValueError("progress.style".to_string())),
+        },
+        _ => return Err(ConfigError::TypeError("progress.style".to_string())),
+    };
+
+    Ok(Progress {
+        period,
+        style,
+        width: integer_value(&item, "width", 100),
+        height: integer_value(&item, "height", 8),
+        thickness: integer_value(&item, "thickness", 4),
+    })
+}
+
+fn make_shift(item: Table) -> std::result::Result<Shift, ConfigError> {
+    let mode = match item.get("mode")? {
+        Value::Nil => ShiftMode::Orbit,
+        Value::String(s) => match s.to_str()? {
+            "orbit" => ShiftMode::Orbit,
+            "random" => ShiftMode::Random,
+            _ => return Err(ConfigError::InvalidValueError("shift.mode".to_string())),
+        },
+        _ => return Err(ConfigError::TypeError("shift.mode".to_string())),
+    };
+
+    Ok(Shift {
+        interval: integer_value(&item, "interval", 10),
+        range: integer_value(&item, "range", 4),
+        mode,
+    })
+}
+
+fn make_power(item: Table) -> std::result::Result<Power, ConfigError> {
+    let keep_on = match item.get("keep_on")? {
+        Value::Nil => true,
+        Value::Boolean(b) => b,
+        _ => return Err(ConfigError::TypeError("power.keep_on".to_string())),
+    };
+
+    let time = |key: &str| -> std::result::Result<Option<(u32, u32)>, ConfigError> {
+        match string_value(&item, key) {
+            Some(text) => match alarm::parse_time(&text) {
+                Some(t) => Ok(Some(t)),
+                None => Err(ConfigError::InvalidValueError(format!("power.{}", key))),
+            },
+            None => Ok(None),
+        }
+    };
+    let off = time("off")?;
+    let on = time("on")?;
+    match (off, on) {
+        (Some(_), None) => return Err(ConfigError::NilValueError("power.on".to_string())),
+        (None, Some(_)) => return Err(ConfigError::NilValueError("power.off".to_string())),
+        _ => (),
+    }
+
+    Ok(Power {
+        keep_on,
+        off,
+        on,
+        wake: integer_value(&item, "wake", 30),
+    })
+}
+
+fn make_night(item: Table) -> std::result::Result<Night, ConfigError> {
+    let time = |key: &str| -> std::result::Result<(u32, u32), ConfigError> {
+        let text = string_value(&item, key)
+            .ok_or_else(|| ConfigError::NilValueError(format!("night.{}", key)))?;
+        alarm::parse_time(&text)
+            .ok_or_else(|| ConfigError::InvalidValueError(format!("night.{}", key)))
+    };
+    let start = time("start")?;
+    let stop = time("stop")?;
+
+    let mode = match item.get("mode")? {
+        Value::Nil => NightMode::Minimal,
+        Value::String(s) => match s.to_str()? {
+            "minimal" => NightMode::Minimal,
+            "blank" => NightMode::Blank,
+            _ => return Err(ConfigError::InvalidValueError("night.mode".to_string())),
+        },
+        _ => return Err(ConfigError::TypeError("night.mode".to_string())),
+    };
+
+    Ok(Night {
+        start,
+        stop,
+        mode,
+        wake: integer_value(&item, "wake", 30),
+    })
+}
+
+fn make_ambient(item: Table) -> std::result::Result<Ambient, ConfigError> {
+    let mut curve = Vec::new();
+    match item.get("curve")? {
+        Value::Nil => (),
+        Value::Table(t) => {
+            for point in t.sequence_values::<Table>() {
+                let point = point?;
+                let lux = number_value(&point, 1)
+                    .ok_or_else(|| ConfigError::TypeError("ambient.curve".to_string()))?;
+                let percent = number_value(&point, 2)
+                    .ok_or_else(|| ConfigError::TypeError("ambient.curve".to_string()))?;
+                if !(0.0..=100.0).contains(&percent) {
+                    return Err(ConfigError::InvalidValueError("ambient.curve".to_string()));
+                }
+                curve.push((lux, percent as u32));
+            }
+        }
+        _ => return Err(ConfigError::TypeError("ambient.curve".to_string())),
+    }
+    curve.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    let smoothing = number_value(&item, "smoothing").unwrap_or(0.2);
+    if smoothing <= 0.0 || smoothing > 1.0 {
+        return Err(ConfigError::InvalidValueError(
+            "ambient.smoothing".to_string(),
+        ));
+    }
+
+    Ok(Ambient {
+        sensor: string_value(&item, "sensor"),
+        scale: number_value(&item, "scale").unwrap_or(1.0),
+        smoothing,
+        curve,
+        dim_below: number_value(&item, "dim_below"),
+    })
+}
+
+fn make_calendar(item: Table) -> std::result::Result<Calendar, ConfigError> {
+    // index into the days table: 1 = Sunday, 2 = Monday, ...
+    let first_day = match item.get("first_day")? {
+        Value::Nil => 0,
+        Value::Integer(n) if (1..=7).contains(&n) => (n - 1) as u32,
+        Value::Integer(_) => {
+            return Err(ConfigError::InvalidValueError(
+                "calendar.first_day".to_string(),
+            ))
+        }
+        _ => return Err(ConfigError::TypeError("calendar.first_day".to_string())),
+    };
+
+    Ok(Calendar {
+        first_day,
+        column: integer_value(&item, "column", 0),
+        row: integer_value(&item, "row", 0),
+    })
+}
+
+fn make_zones(item: Table) -> std::result::Result<Vec<Zone>, ConfigError> {
+    let mut zones = Vec::new();
+    for value in item.sequence_values::<Table>() {
+        let value = value?;
+
+        let zone_name = match string_value(&value, "zone") {
+            Some(z) => z,
+            None => return Err(ConfigError::NilValueError("zones.zone".to_string())),
+        };
+        let zone = match zone_name.parse::<Tz>() {
+            Ok(z) => z,
+            Err(_) => {
+                return Err(ConfigError::InvalidValueError(format!(
+                    "zones: {}",
+                    zone_name
+                )))
+            }
+        };
+
+        // a bad specifier would only fail when the clock is drawn
+        let format = string_value(&value, "format").unwrap_or_else(|| "%H:%M".to_string());
+        if StrftimeItems::new(&format).any(|i| matches!(i, Item::Error)) {
+            return Err(ConfigError::InvalidValueError(format!(
+                "zones.format: {}",
+                format
+            )));
+        }
+
+        zones.push(Zone {
+            name: string_value(&value, "name").unwrap_or_else(|| zone_name.clone()),
+            zone,
+            label: string_value(&value, "label").unwrap_or_default(),
+            format,
+        });
+    }
+    Ok(zones)
+}
+
+fn make_alarms(item: Table) -> std::result::Result<Vec<Alarm>, ConfigError> {
+    let mut alarms = Vec::new();
+    for value in item.sequence_values::<Table>() {
+        let value = value?;
+
+        let time = match string_value(&value, "time") {
+            Some(t) => t,
+            None => return Err(ConfigError::NilValueError("alarms.time".to_string())),
+        };
+        let label = string_value(&value, "label").unwrap_or_default();
+        let mut alarm = match Alarm::new(&time, &label) {
+            Some(a) => a,
+            None => return Err(ConfigError::InvalidValueError(format!("alarms: {}", time))),
+        };
+
+        // days are indices into the days table: 1 = Sunday, 2 = Monday, ...
+        match value.get("days")? {
+            Value::Nil => (),
+            Value::Table(t) => {
+                alarm.days = [false; 7];
+                for d in t.sequence_values::<i64>() {
+                    match d? {
+                        n @ 1..=7 => alarm.days[(n - 1) as usize] = true,
+                        _ => return Err(ConfigError::InvalidValueError("alarms.days".to_string())),
+                    }
+                }
+            }
+            _ => return Err(ConfigError::TypeError("alarms.days".to_string())),
+        }
+
+        alarms.push(alarm);
+    }
+    Ok(alarms)
+}
+
+// the widgets a page can show, besides the zones by name
+const WIDGETS: [&str; 7] = [
+    "time", "day", "date", "weather", "progress", "calendar", "timer",
+];
+
+// each page is a list of widget names
+fn make_pages(item: Table, zones: &[Zone]) -> std::result::Result<Vec<Vec<String>>, ConfigError> {
+    let mut pages = Vec::new();
+    for page in item.sequence_values::<Table>() {
+        let mut names = Vec::new();
+        for name in page?.sequence_values::<String>() {
+            let name = name?;
+            // a misspelt name would just leave a blank page
+            if !WIDGETS.contains(&name.as_str()) && !zones.iter().any(|z| z.name == name) {
+                return Err(ConfigError::InvalidValueError(format!("pages: {}", name)));
+            }
+            names.push(name);
+        }
+        pages.push(names);
+    }
+    Ok(pages)
+}
+
+// entries are added to, or replace, the default bindings
+fn make_bindings(item: Table) -> std::result::Result<Bindings, ConfigError> {
+    let mut bindings = Bindings::default();
+
+    if let Value::Table(keys) = item.get("keys")? {
+        for pair in keys.pairs::<String, String>() {
+            let (key, value) = pair?;
+            let action = binding_action(&value, "bindings.keys")?;
+            bindings.keys.insert(key, action);
+        }
+    }
+
+    if let Value::Table(buttons) = item.get("buttons")? {
+        for pair in buttons.pairs::<u32, String>() {
+            let (button, value) = pair?;
+            // it would act on press as well as making a gesture
+            if button == gesture::BUTTON {
+                let message = format!("bindings.buttons: {} is used for gestures", button);
+                return Err(ConfigError::InvalidValueError(message));
+            }
+            let action = binding_action(&value, "bindings.buttons")?;
+            bindings.buttons.insert(button, action);
+        }
+    }
+
+    if let Value::Table(gestures) = item.get("gestures")? {
+        for pair in gestures.pairs::<String, String>() {
+            let (name, value) = pair?;
+            let gesture = match Gesture::parse(&name) {
+                Some(g) => g,
+                None => {
+                    let message = format!("bindings.gestures: {}", name);
+                    return Err(ConfigError::InvalidValueError(message));
+                }
+            };
+            let action = binding_action(&value, "bindings.gestures")?;
+            bindings.gestures.insert(gesture, action);
+        }
+    }
+
+    Ok(bindings)
+}
+
+// "none" unbinds
+fn binding_action(text: &str, name: &str) -> std::result::Result<Option<Action>, ConfigError> {
+    if text == "none" {
+        return Ok(None);
+    }
+    match Action::parse(text) {
+        Some(action) => Ok(Some(action)),
+        None => Err(ConfigError::InvalidValueError(format!(
+            "{}: {}",
+            name, text
+        ))),
+    }
+}
+
+// fetch a string field from a table, anything else is None
+fn string_value(item: &Table, key: &str) -> Option<String> {
+    match item.get(key) {
+        Ok(Value::String(s)) => s.to_str().ok().map(|s| s.to_string()),
+        _ => None,
+    }
+}
+
+// fetch an integer or floating point field, None if neither
+fn number_value<'lua, K: ToLua<'lua>>(item: &Table<'lua>, key: K) -> Option<f64> {
+    match item.get(key) {
+        Ok(Value::Integer(n)) => Some(n as f64),
+        Ok(Value::Number(n)) => Some(n),
+        _ => None,
+    }
+}
+
+// fetch an integer field from a table, zero or negative values
+// and anything that is not an integer give the default
+fn integer_value(item: &Table, key: &str, default: i32) -> i32 {
+    match item.get(key) {
+        Ok(Value::Integer(n)) if n > 0 => n as i32,
+        _ => default,
+    }
+}
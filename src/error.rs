@@ -0,0 +1,80 @@
+// error.rs
+
+// reasons the clock cannot start or keep running, each with its own
+// exit status (from sysexits.h) so a restart loop or systemd can tell a
+// configuration that will fail again from a display that may come back
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+const EX_UNAVAILABLE: i32 = 69;
+const EX_OSERR: i32 = 71;
+const EX_CANTCREAT: i32 = 73;
+const EX_TEMPFAIL: i32 = 75;
+const EX_CONFIG: i32 = 78;
+
+#[derive(Debug)]
+pub enum StartupError {
+    NoConfigDirectory,
+    Config(PathBuf, Box<dyn std::error::Error>),
+    Display,
+    X(String), // a request the server refused
+    Font(String),
+    Colour(String),
+    Socket(String, io::Error),
+    Lock(String, io::Error),
+    Running(String, bool), // another clock has the socket, asked to quit
+    System(&'static str, io::Error),
+}
+
+impl StartupError {
+    /// The status to exit with: EX_CONFIG when restarting cannot help
+    /// until the configuration or installation is fixed
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            StartupError::NoConfigDirectory => EX_CONFIG,
+            StartupError::Config(_, _) => EX_CONFIG,
+            StartupError::Display => EX_UNAVAILABLE,
+            StartupError::X(_) => EX_UNAVAILABLE,
+            StartupError::Font(_) => EX_CONFIG,
+            StartupError::Colour(_) => EX_UNAVAILABLE,
+            StartupError::Socket(_, _) => EX_CANTCREAT,
+            StartupError::Lock(_, _) => EX_CANTCREAT,
+            StartupError::Running(_, _) => EX_TEMPFAIL,
+            StartupError::System(_, _) => EX_OSERR,
+        }
+    }
+}
+
+impl fmt::Display for StartupError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StartupError::NoConfigDirectory => {
+                write!(fmt, "cannot find the configuration directory, use --config")
+            }
+            StartupError::Config(ref path, ref e) => {
+                write!(fmt, "error in config file {}: {}", path.display(), e)
+            }
+            StartupError::Display => write!(fmt, "cannot open display"),
+            StartupError::X(ref message) => write!(fmt, "X server: {}", message),
+            StartupError::Font(ref message) => write!(fmt, "{}", message),
+            StartupError::Colour(ref message) => write!(fmt, "{}", message),
+            StartupError::Socket(ref path, ref e) => {
+                write!(fmt, "cannot listen on socket {}: {}", path, e)
+            }
+            StartupError::Lock(ref path, ref e) => write!(fmt, "cannot lock {}: {}", path, e),
+            StartupError::Running(ref socket, false) => write!(
+                fmt,
+                "another clock is running on {}, use --replace to take over",
+                socket
+            ),
+            StartupError::Running(ref socket, true) => {
+                write!(fmt, "the clock running on {} did not quit", socket)
+            }
+            StartupError::System(what, ref e) => write!(fmt, "cannot set up {}: {}", what, e),
+        }
+    }
+}
+
+impl std::error::Error for StartupError {}
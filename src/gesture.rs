@@ -0,0 +1,143 @@
+// gesture.rs
+
+// turn press, motion and release of a touch (or the first mouse
+// button) into simple gestures
+
+// distances in pixels, times in milliseconds (X server time)
+const SWIPE_DISTANCE: f64 = 60.0;
+const TAP_DISTANCE: f64 = 20.0;
+const LONG_PRESS_TIME: u64 = 800;
+
+// the button (or touch) gestures are made with, which cannot also be
+// bound directly
+pub const BUTTON: u32 = 1;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Gesture {
+    Tap,
+    LongPress,
+    SwipeLeft,
+    SwipeRight,
+}
+
+impl Gesture {
+    /// The gesture names used in the bindings table
+    pub fn parse(name: &str) -> Option<Gesture> {
+        match name {
+            "tap" => Some(Gesture::Tap),
+            "long_press" => Some(Gesture::LongPress),
+            "swipe_left" => Some(Gesture::SwipeLeft),
+            "swipe_right" => Some(Gesture::SwipeRight),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Touch {
+    x: f64,
+    y: f64,
+    time: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct Recogniser {
+    start: Option<Touch>,
+}
+
+impl Recogniser {
+    pub fn new() -> Recogniser {
+        Recogniser { start: None }
+    }
+
+    pub fn press(&mut self, x: f64, y: f64, time: u64) {
+        self.start = Some(Touch { x, y, time });
+    }
+
+    /// Classify the touch ending at (x, y); None if it was neither a
+    /// clear swipe nor a stationary press
+    pub fn release(&mut self, x: f64, y: f64, time: u64) -> Option<Gesture> {
+        let start = self.start.take()?;
+
+        let dx = x - start.x;
+        let dy = y - start.y;
+
+        if dx.abs() >= SWIPE_DISTANCE && dx.abs() > 2.0 * dy.abs() {
+            if dx < 0.0 {
+                Some(Gesture::SwipeLeft)
+            } else {
+                Some(Gesture::SwipeRight)
+            }
+        } else if dx.abs() <= TAP_DISTANCE && dy.abs() <= TAP_DISTANCE {
+            if time.wrapping_sub(start.time) >= LONG_PRESS_TIME {
+                Some(Gesture::LongPress)
+            } else {
+                Some(Gesture::Tap)
+            }
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a touch at time 1000 that moves by (dx, dy) and ends |held| later
+    fn touch(dx: f64, dy: f64, held: u64) -> Option<Gesture> {
+        let mut recogniser = Recogniser::new();
+        recogniser.press(100.0, 100.0, 1000);
+        recogniser.release(100.0 + dx, 100.0 + dy, 1000 + held)
+    }
+
+    #[test]
+    fn tap() {
+        assert_eq!(touch(0.0, 0.0, 100), Some(Gesture::Tap));
+        assert_eq!(touch(TAP_DISTANCE, -TAP_DISTANCE, 100), Some(Gesture::Tap));
+        assert_eq!(touch(TAP_DISTANCE + 1.0, 0.0, 100), None);
+        assert_eq!(touch(0.0, -TAP_DISTANCE - 1.0, 100), None);
+    }
+
+    #[test]
+    fn long_press() {
+        assert_eq!(touch(5.0, 5.0, LONG_PRESS_TIME), Some(Gesture::LongPress));
+        assert_eq!(touch(5.0, 5.0, LONG_PRESS_TIME - 1), Some(Gesture::Tap));
+        assert_eq!(touch(TAP_DISTANCE + 1.0, 0.0, LONG_PRESS_TIME), None);
+    }
+
+    #[test]
+    fn swipe() {
+        assert_eq!(touch(-SWIPE_DISTANCE, 0.0, 100), Some(Gesture::SwipeLeft));
+        assert_eq!(touch(SWIPE_DISTANCE, 0.0, 100), Some(Gesture::SwipeRight));
+        assert_eq!(touch(SWIPE_DISTANCE - 1.0, 0.0, 100), None);
+        // however long it takes
+        assert_eq!(touch(200.0, 10.0, 5000), Some(Gesture::SwipeRight));
+    }
+
+    #[test]
+    fn diagonal() {
+        assert_eq!(touch(80.0, 39.0, 100), Some(Gesture::SwipeRight));
+        assert_eq!(touch(80.0, 40.0, 100), None);
+        assert_eq!(touch(-80.0, -60.0, 100), None);
+        assert_eq!(touch(0.0, 200.0, 100), None);
+    }
+
+    #[test]
+    fn time_wraps() {
+        let mut recogniser = Recogniser::new();
+        recogniser.press(0.0, 0.0, u64::MAX - 99);
+        assert_eq!(recogniser.release(0.0, 0.0, 700), Some(Gesture::LongPress));
+        recogniser.press(0.0, 0.0, u64::MAX - 99);
+        assert_eq!(recogniser.release(0.0, 0.0, 100), Some(Gesture::Tap));
+    }
+
+    #[test]
+    fn release_without_press() {
+        let mut recogniser = Recogniser::new();
+        assert_eq!(recogniser.release(0.0, 0.0, 0), None);
+        recogniser.press(0.0, 0.0, 0);
+        assert_eq!(recogniser.release(0.0, 0.0, 10), Some(Gesture::Tap));
+        assert_eq!(recogniser.release(0.0, 0.0, 20), None);
+    }
+}
@@ -0,0 +1,52 @@
+// lock.rs
+
+// one clock per socket: an flock on a file beside the socket, held for
+// as long as the clock runs and released by the kernel however it exits
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how often to retry while waiting for another clock to go
+const RETRY: Duration = Duration::from_millis(100);
+
+pub struct Lock {
+    _file: File, // closing it releases the lock
+}
+
+impl Lock {
+    /// Take the lock without waiting, None if another clock holds it
+    pub fn take(path: &str) -> io::Result<Option<Lock>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(path)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+            return Ok(Some(Lock { _file: file }));
+        }
+        let e = io::Error::last_os_error();
+        if e.kind() == io::ErrorKind::WouldBlock {
+            Ok(None)
+        } else {
+            Err(e)
+        }
+    }
+
+    /// Take the lock once the clock holding it has gone, None if it is
+    /// still there after |timeout|
+    pub fn wait(path: &str, timeout: Duration) -> io::Result<Option<Lock>> {
+        let start = Instant::now();
+        loop {
+            if let Some(lock) = Lock::take(path)? {
+                return Ok(Some(lock));
+            }
+            if start.elapsed() >= timeout {
+                return Ok(None);
+            }
+            thread::sleep(RETRY);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+// logger.rs
+
+// the log backend: timestamped lines on stderr or in a log file, and
+// optionally syslog (which journald also collects)
+
+use chrono::prelude::*;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::raw::c_char;
+use std::sync::Mutex;
+
+const IDENT: &[u8] = b"rusty-clock\0";
+
+struct Logger {
+    file: Option<Mutex<File>>, // None: stderr
+    syslog: bool,
+}
+
+/// The level for a number of -v flags: warnings and errors only by
+/// default, then info, debug and trace
+pub fn level(verbosity: u64) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Install the logger, appending to |file| if given; one that cannot
+/// be opened is reported, and stderr used instead
+pub fn init(level: LevelFilter, file: Option<&str>, syslog: bool) {
+    let mut failure = None;
+    let mut log_file = None;
+    if let Some(path) = file {
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(f) => log_file = Some(Mutex::new(f)),
+            Err(e) => failure = Some(format!("cannot open log file {}: {}", path, e)),
+        }
+    }
+    if syslog {
+        unsafe {
+            libc::openlog(
+                IDENT.as_ptr() as *const c_char,
+                libc::LOG_PID,
+                libc::LOG_DAEMON,
+            );
+        }
+    }
+
+    // only fails if a logger is already installed
+    let logger = Logger {
+        file: log_file,
+        syslog,
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+    if let Some(message) = failure {
+        log::warn!("{}", message);
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        match &self.file {
+            Some(file) => {
+                if let Ok(mut f) = file.lock() {
+                    f.write_all(line.as_bytes()).ok();
+                }
+            }
+            None => {
+                io::stderr().write_all(line.as_bytes()).ok();
+            }
+        }
+
+        if self.syslog {
+            let priority = match record.level() {
+                Level::Error => libc::LOG_ERR,
+                Level::Warn => libc::LOG_WARNING,
+                Level::Info => libc::LOG_INFO,
+                Level::Debug | Level::Trace => libc::LOG_DEBUG,
+            };
+            if let Ok(message) = CString::new(record.args().to_string()) {
+                unsafe {
+                    libc::syslog(
+                        priority,
+                        b"%s\0".as_ptr() as *const c_char,
+                        message.as_ptr(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut f) = file.lock() {
+                f.flush().ok();
+            }
+        }
+    }
+}
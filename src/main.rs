@@ -4,15 +4,33 @@ use chrono::prelude::*;
 use clap::{load_yaml, App};
 use dirs;
 use libc;
+use log::{debug, error, info, trace, warn};
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::mem::{transmute, zeroed};
 use std::os::raw::*;
-use std::ptr::{null, null_mut};
-use std::sync::{Arc, Mutex};
-use x11::{xft, xinput2, xlib};
+use std::ptr::null_mut;
+use std::rc::Rc;
 
+use error::StartupError;
+use x11::{xft, xinput2, xlib, xrandr};
+
+mod action;
+mod alarm;
+mod ambient;
+mod backlight;
 mod configure;
+mod error;
+mod gesture;
+mod lock;
+mod logger;
+mod output;
+mod power;
+mod resource;
+mod signal;
 mod socket;
+mod tick;
+mod timer;
 
 const TITLE: &'static str = "Rusty Clock";
 const DEFAULT_WIDTH: u32 = 480;
@@ -26,51 +44,214 @@ const DATE_MARGIN: i32 = (DEFAULT_WIDTH / 2) as i32;
 const DATE_Y: i32 = 200;
 const WEATHER_MARGIN: i32 = DEFAULT_MARGIN;
 const WEATHER_Y: i32 = 300;
+const PROGRESS_MARGIN: i32 = DEFAULT_MARGIN;
+const PROGRESS_Y: i32 = 120;
+const CALENDAR_MARGIN: i32 = DEFAULT_MARGIN;
+const CALENDAR_Y: i32 = 30;
+const CALENDAR_PADDING: i32 = 6;
+const ZONE_MARGIN: i32 = DEFAULT_MARGIN;
+const ZONE_Y: i32 = 40;
+const ZONE_STEP: i32 = 40;
+
+// only changed areas are copied from the pixmap, so the rest has to be
+// restored when the window is uncovered; the pixmap follows the window size
+const WINDOW_EVENTS: c_long = xlib::ExposureMask | xlib::StructureNotifyMask;
+
+// how long --replace waits for the running clock to quit
+const REPLACE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 const DEFAULT_CONFIG_DIR: &str = "rusty-clock";
 const DEFAULT_CONFIG_FILE: &str = "rusty-clock.conf";
 
+#[derive(Clone)]
 struct Theme {
-    time: x11::xft::XftColor,
-    day: x11::xft::XftColor,
-    date: x11::xft::XftColor,
-    weather: x11::xft::XftColor,
-    background: x11::xft::XftColor,
+    time: Rc<resource::Colour>,
+    day: Rc<resource::Colour>,
+    date: Rc<resource::Colour>,
+    weather: Rc<resource::Colour>,
+    progress: Rc<resource::Colour>,
+    calendar: Rc<resource::Colour>,
+    today: Rc<resource::Colour>,
+    zone: Rc<resource::Colour>,
+    timer: Rc<resource::Colour>,
+    background: Rc<resource::Colour>,
+    brightness: Option<u32>, // backlight percent
+}
+
+// a clock for another time zone
+struct ZoneClock {
+    zone: configure::Zone,
+    font: resource::Font,
+    point: configure::Point,
+}
+
+// a rectangle of the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl Rect {
+    fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.x + other.width as i32
+            && other.x < self.x + self.width as i32
+            && self.y < other.y + other.height as i32
+            && other.y < self.y + self.height as i32
+    }
+}
+
+// how to draw a widget
+enum Content {
+    Text(
+        x11::xft::XftColor,
+        *mut x11::xft::XftFont,
+        configure::Point,
+        String,
+    ),
+    Progress(
+        configure::Point,
+        configure::Progress,
+        x11::xft::XftColor,
+        f64,
+    ),
+    Calendar(configure::Point, configure::Calendar, NaiveDate),
+}
+
+// a widget ready to draw; |key| changes whenever the drawing would, so
+// an unchanged key and area means the pixmap already holds it
+struct Widget {
+    name: String,
+    key: String,
+    area: Rect,
+    content: Content,
+}
+
+impl Widget {
+    // move the whole widget, for burn-in protection
+    fn translate(&mut self, dx: i32, dy: i32) {
+        self.area.x += dx;
+        self.area.y += dy;
+        let point = match &mut self.content {
+            Content::Text(_, _, point, _) => point,
+            Content::Progress(point, _, _, _) => point,
+            Content::Calendar(point, _, _) => point,
+        };
+        point.x += dx;
+        point.y += dy;
+    }
+}
+
+impl Theme {
+    // every foreground on the time colour, for flashing an alert
+    fn inverted(&self) -> Theme {
+        let foreground = &self.background;
+        Theme {
+            time: foreground.clone(),
+            day: foreground.clone(),
+            date: foreground.clone(),
+            weather: foreground.clone(),
+            progress: foreground.clone(),
+            calendar: foreground.clone(),
+            today: foreground.clone(),
+            zone: foreground.clone(),
+            timer: foreground.clone(),
+            background: self.time.clone(),
+            brightness: self.brightness,
+        }
+    }
 }
 
 pub struct ClockWindow {
     pub display: *mut xlib::Display,
     pub window: xlib::Window,
 
-    pixmap: xlib::Pixmap,
-    draw: *mut x11::xft::XftDraw,
-    gc: xlib::GC,
+    draw: resource::Draw,
+    pixmap: resource::Pixmap,
+    gc: resource::Gc,
 
-    time_font: *mut x11::xft::XftFont,
-    day_font: *mut x11::xft::XftFont,
-    date_font: *mut x11::xft::XftFont,
-    weather_font: *mut x11::xft::XftFont,
+    time_font: resource::Font,
+    day_font: resource::Font,
+    date_font: resource::Font,
+    weather_font: resource::Font,
+    calendar_font: resource::Font,
+    alert_font: resource::Font,
+    timer_font: resource::Font,
 
     time_point: configure::Point,
     day_point: configure::Point,
     date_point: configure::Point,
     weather_point: configure::Point,
+    progress_point: configure::Point,
+    calendar_point: configure::Point,
+    alert_point: configure::Point,
+    timer_point: Option<configure::Point>, // None: in place of the time
+
+    progress: Option<configure::Progress>,
+    calendar: Option<configure::Calendar>,
+    zones: Vec<ZoneClock>,
+
+    alarms: Vec<alarm::Alarm>,
+    alarm_command: Option<String>,
+    alarm_checked: Option<NaiveDateTime>,
 
     days: [String; 7],
 
+    pages: Vec<Vec<String>>, // empty: a single page of every widget
+    page: usize,
+    idle_timeout: Option<std::time::Duration>,
+    last_touch: std::time::Instant,
+
+    blank_cursor: resource::Cursor,
+    hide_cursor: Option<bool>, // None: hide when fullscreen
+    cursor_timeout: Option<std::time::Duration>,
+    cursor_hidden: bool,
+
+    keys: HashMap<xlib::KeySym, action::Action>,
+    buttons: HashMap<u32, action::Action>,
+    gestures: HashMap<gesture::Gesture, action::Action>,
+
+    dimmed: bool,
+    is_fullscreen: bool,
+    shift: Option<configure::Shift>,
+
+    backlight: Option<backlight::Backlight>,
+    scheduled_brightness: Option<u32>, // the theme's level last time
+    ambient: Option<ambient::Ambient>,
+    night: Option<configure::Night>,
+    awake_until: Option<std::time::Instant>, // woken by a touch at night
+    power: power::Power,
+
+    drawn: HashMap<String, (String, Rect)>, // key and area by widget name
+    drawn_background: Option<c_ulong>,      // None: repaint everything
+
     early: Theme,
     morning: Theme,
     afternoon: Theme,
     evening: Theme,
     unsync: Theme,
+    alert: Theme,
+    expired: Theme,
+    dim: Theme,
+    night_theme: Theme,
+
+    output: Option<String>, // XRandR output to follow
+    randr_event_base: Option<c_int>,
+    hidden: bool, // unmapped while the output is disconnected
 
     width: u32,
     height: u32,
+    layout_width: u32, // the size the coordinates were configured for
+    layout_height: u32,
 
     wm_protocols: xlib::Atom,
     wm_delete_window: xlib::Atom,
 
-    input: Arc<Mutex<socket::Input>>,
+    input: socket::Input,
+
+    connection: Rc<resource::Display>, // closed once everything above is freed
 }
 
 impl ClockWindow {
@@ -79,18 +260,17 @@ impl ClockWindow {
         title: &str,
         width: u32,
         height: u32,
-        days: [String; 7],
-        fonts: configure::StrMap,
-        coordinates: configure::PointMap,
-        themes: configure::ThemeMap,
-        input: Arc<Mutex<socket::Input>>,
-    ) -> ClockWindow {
+        cfg: configure::Configuration,
+        input: socket::Input,
+    ) -> Result<ClockWindow, StartupError> {
+        let fonts = cfg.fonts;
+        let coordinates = cfg.coordinates;
+        let themes = cfg.themes;
+
         unsafe {
             // Open display
-            let display = xlib::XOpenDisplay(null());
-            if display == null_mut() {
-                panic!("can't open display");
-            }
+            let connection = resource::Display::open().ok_or(StartupError::Display)?;
+            let display = connection.raw();
 
             // Load atoms
             let wm_delete_window_str = CString::new("WM_DELETE_WINDOW").unwrap();
@@ -102,7 +282,7 @@ impl ClockWindow {
             let wm_protocols = xlib::XInternAtom(display, wm_protocols_str.as_ptr(), xlib::False);
 
             if wm_delete_window == 0 || wm_protocols == 0 {
-                panic!("can't load atoms");
+                return Err(StartupError::X("cannot load atoms".to_string()));
             }
 
             // Create window
@@ -111,16 +291,43 @@ impl ClockWindow {
             let background_pixel = xlib::XBlackPixel(display, screen_num);
             let foreground_pixel = xlib::XWhitePixel(display, screen_num);
 
+            // on a chosen output the window takes that output's size,
+            // and stays hidden until it is connected
+            let randr_event_base = output::select_changes(display, root);
+            let geometry = match &cfg.output {
+                Some(name) => {
+                    let found = output::find(display, root, name);
+                    if found.is_none() {
+                        let names: Vec<String> = output::list(display, root)
+                            .into_iter()
+                            .map(|(n, _)| n)
+                            .collect();
+                        warn!(
+                            "output {} is not connected, available: {}",
+                            name,
+                            names.join(" ")
+                        );
+                    }
+                    found
+                }
+                None => None,
+            };
+            let hidden = cfg.output.is_some() && geometry.is_none();
+            let (x, y, window_width, window_height) = match geometry {
+                Some(g) => (g.x, g.y, g.width, g.height),
+                None => (0, 0, width, height),
+            };
+
             let mut attributes: xlib::XSetWindowAttributes = zeroed();
             attributes.background_pixel = background_pixel;
 
             let window = xlib::XCreateWindow(
                 display,
                 root,
-                0,
-                0,
-                width as c_uint,
-                height as c_uint,
+                x,
+                y,
+                window_width as c_uint,
+                window_height as c_uint,
                 0,
                 0,
                 xlib::InputOutput as c_uint,
@@ -129,6 +336,15 @@ impl ClockWindow {
                 &mut attributes,
             );
 
+            // keep the window manager from placing it somewhere else
+            let mut hints: xlib::XSizeHints = zeroed();
+            hints.flags = xlib::USPosition | xlib::USSize;
+            hints.x = x;
+            hints.y = y;
+            hints.width = window_width as c_int;
+            hints.height = window_height as c_int;
+            xlib::XSetWMNormalHints(display, window, &mut hints);
+
             // Set window title
             let title_str = CString::new(title).unwrap();
             xlib::XStoreName(display, window, title_str.as_ptr() as *mut _);
@@ -139,65 +355,147 @@ impl ClockWindow {
             if xlib::XSetWMProtocols(display, window, &mut protocols[0] as *mut xlib::Atom, 1)
                 == xlib::False
             {
-                panic!("can't set WM protocols");
+                return Err(StartupError::X("cannot set WM protocols".to_string()));
             }
 
+            xlib::XSelectInput(display, window, WINDOW_EVENTS);
+
             let visual = xlib::XDefaultVisual(display, screen_num);
-            let colourmap = xlib::XCreateColormap(display, window, visual, xlib::AllocNone);
-            //let draw = xft::XftDrawCreate(display, window, visual, colourmap);
+            let colourmap = resource::Colourmap::new(&connection, window, visual);
 
             let depth = xlib::XDefaultDepthOfScreen(xlib::XDefaultScreenOfDisplay(display));
-            let pixmap = xlib::XCreatePixmap(display, window, width, height, depth as u32);
-            //let draw = xft::XftDrawCreateAlpha(display, pixmap, depth);
-            let draw = xft::XftDrawCreate(display, pixmap, visual, colourmap);
-
-            let mut gcvalues: xlib::XGCValues = zeroed();
-            let gc = xlib::XCreateGC(display, window, 0, &mut gcvalues);
-            //xlib:: XSetFunction(self.display, gc, int function);
+            let pixmap = resource::Pixmap::new(
+                &connection,
+                window,
+                window_width,
+                window_height,
+                depth as u32,
+            );
+            let draw = resource::Draw::new(&pixmap, &colourmap);
 
-            xlib::XSetPlaneMask(display, gc, xlib::XAllPlanes());
-            xlib::XSetForeground(display, gc, foreground_pixel);
-            xlib::XSetBackground(display, gc, background_pixel);
+            let gc = resource::Gc::new(&connection, window);
+            xlib::XSetPlaneMask(display, gc.raw(), xlib::XAllPlanes());
+            xlib::XSetForeground(display, gc.raw(), foreground_pixel);
+            xlib::XSetBackground(display, gc.raw(), background_pixel);
 
             let time_font = ClockWindow::make_font(
-                display,
+                &connection,
                 screen_num,
-                fonts
-                    .get("time")
-                    .unwrap_or(&"Noto Sans:style=bold:size=89".to_string()),
-            );
+                "time",
+                fonts.get("time"),
+                "Noto Sans:style=bold:size=89",
+            )?;
             let day_font = ClockWindow::make_font(
-                display,
+                &connection,
                 screen_num,
-                fonts
-                    .get("day")
-                    .unwrap_or(&"Noto Sans:style=bold:size=60".to_string()),
-            );
+                "day",
+                fonts.get("day"),
+                "Noto Sans:style=bold:size=60",
+            )?;
             let date_font = ClockWindow::make_font(
-                display,
+                &connection,
                 screen_num,
-                fonts
-                    .get("date")
-                    .unwrap_or(&"Noto Sans:style=bold:size=60".to_string()),
-            );
+                "date",
+                fonts.get("date"),
+                "Noto Sans:style=bold:size=60",
+            )?;
             let weather_font = ClockWindow::make_font(
-                display,
+                &connection,
                 screen_num,
-                fonts
-                    .get("weather")
-                    .unwrap_or(&"Noto Sans:style=bold:size=50".to_string()),
-            );
+                "weather",
+                fonts.get("weather"),
+                "Noto Sans:style=bold:size=50",
+            )?;
+            let calendar_font = ClockWindow::make_font(
+                &connection,
+                screen_num,
+                "calendar",
+                fonts.get("calendar"),
+                "Noto Sans:style=bold:size=16",
+            )?;
+
+            let alert_font = ClockWindow::make_font(
+                &connection,
+                screen_num,
+                "alert",
+                fonts.get("alert").or_else(|| fonts.get("weather")),
+                "Noto Sans:style=bold:size=50",
+            )?;
 
-            ClockWindow {
+            let timer_font = ClockWindow::make_font(
+                &connection,
+                screen_num,
+                "timer",
+                fonts.get("timer").or_else(|| fonts.get("time")),
+                "Noto Sans:style=bold:size=89",
+            )?;
+
+            // each zone can have its own font, or share a common one
+            let zone_font = fonts.get("zone");
+            let zones = cfg
+                .zones
+                .into_iter()
+                .enumerate()
+                .map(|(i, zone)| {
+                    Ok(ZoneClock {
+                        font: ClockWindow::make_font(
+                            &connection,
+                            screen_num,
+                            &zone.name,
+                            fonts.get(&zone.name).or(zone_font),
+                            "Noto Sans:style=bold:size=30",
+                        )?,
+                        point: *coordinates.get(&zone.name).unwrap_or(&configure::Point {
+                            x: ZONE_MARGIN,
+                            y: ZONE_Y + i as i32 * ZONE_STEP,
+                        }),
+                        zone,
+                    })
+                })
+                .collect::<Result<Vec<_>, StartupError>>()?;
+
+            // keys are bound by keysym name, e.g. "Escape" or "F1"
+            let mut keys = HashMap::new();
+            for (name, action) in cfg.bindings.keys {
+                // a name with a NUL in it cannot be a keysym either
+                let sym = match CString::new(name.as_str()) {
+                    Ok(name_str) => xlib::XStringToKeysym(name_str.as_ptr()),
+                    Err(_) => 0,
+                };
+                match (sym, action) {
+                    (0, _) => warn!("unknown key in bindings: {}", name),
+                    (sym, Some(action)) => {
+                        keys.insert(sym, action);
+                    }
+                    (_, None) => (),
+                }
+            }
+            let buttons = cfg
+                .bindings
+                .buttons
+                .into_iter()
+                .filter_map(|(button, action)| action.map(|a| (button, a)))
+                .collect();
+            let gestures = cfg
+                .bindings
+                .gestures
+                .into_iter()
+                .filter_map(|(gesture, action)| action.map(|a| (gesture, a)))
+                .collect();
+
+            Ok(ClockWindow {
                 display: display,
                 window: window,
-                pixmap: pixmap,
-                draw: draw,
-                gc: gc,
+                draw,
+                pixmap,
+                gc,
                 time_font: time_font,
                 day_font: day_font,
                 date_font: date_font,
                 weather_font: weather_font,
+                calendar_font,
+                alert_font,
+                timer_font,
                 time_point: *coordinates.get("time").unwrap_or(&configure::Point {
                     x: TIME_MARGIN,
                     y: TIME_Y,
@@ -214,135 +512,229 @@ impl ClockWindow {
                     x: WEATHER_MARGIN,
                     y: WEATHER_Y,
                 }),
+                progress_point: *coordinates.get("progress").unwrap_or(&configure::Point {
+                    x: PROGRESS_MARGIN,
+                    y: PROGRESS_Y,
+                }),
 
-                days: days,
+                calendar_point: *coordinates.get("calendar").unwrap_or(&configure::Point {
+                    x: CALENDAR_MARGIN,
+                    y: CALENDAR_Y,
+                }),
 
-                early: ClockWindow::make_theme(
-                    display,
-                    visual,
-                    colourmap,
-                    themes.get("early"),
-                    "SteelBlue",
-                    "grey5",
-                ),
-                morning: ClockWindow::make_theme(
-                    display,
-                    visual,
-                    colourmap,
-                    themes.get("morning"),
-                    "gold",
-                    "black",
-                ),
+                alert_point: *coordinates
+                    .get("alert")
+                    .or_else(|| coordinates.get("weather"))
+                    .unwrap_or(&configure::Point {
+                        x: WEATHER_MARGIN,
+                        y: WEATHER_Y,
+                    }),
+                timer_point: coordinates.get("timer").copied(),
+
+                progress: cfg.progress,
+                calendar: cfg.calendar,
+                zones,
+
+                alarms: cfg.alarms,
+                alarm_command: cfg.alarm_command,
+                alarm_checked: None,
+
+                days: cfg.days,
+
+                pages: cfg.pages,
+                page: 0,
+                idle_timeout: if cfg.idle_timeout > 0 {
+                    Some(std::time::Duration::from_secs(cfg.idle_timeout as u64))
+                } else {
+                    None
+                },
+                last_touch: std::time::Instant::now(),
+                blank_cursor: resource::Cursor::blank(&connection, window),
+                hide_cursor: cfg.hide_cursor,
+                cursor_timeout: if cfg.cursor_timeout > 0 {
+                    Some(std::time::Duration::from_secs(cfg.cursor_timeout as u64))
+                } else {
+                    None
+                },
+                cursor_hidden: false,
+
+                keys,
+                buttons,
+                gestures,
+
+                dimmed: false,
+                is_fullscreen: false,
+
+                early: ClockWindow::make_theme(&colourmap, &themes, "early", "SteelBlue", "grey5")?,
+                morning: ClockWindow::make_theme(&colourmap, &themes, "morning", "gold", "black")?,
                 afternoon: ClockWindow::make_theme(
-                    display,
-                    visual,
-                    colourmap,
-                    themes.get("afternoon"),
+                    &colourmap,
+                    &themes,
+                    "afternoon",
                     "pink",
                     "black",
-                ),
+                )?,
                 evening: ClockWindow::make_theme(
-                    display,
-                    visual,
-                    colourmap,
-                    themes.get("evening"),
+                    &colourmap,
+                    &themes,
+                    "evening",
                     "SpringGreen",
                     "grey4",
-                ),
-                unsync: ClockWindow::make_theme(
-                    display,
-                    visual,
-                    colourmap,
-                    themes.get("unsync"),
-                    "black",
-                    "red",
-                ),
-                width: width,
-                height: height,
+                )?,
+                unsync: ClockWindow::make_theme(&colourmap, &themes, "unsync", "black", "red")?,
+                alert: ClockWindow::make_theme(&colourmap, &themes, "alert", "black", "yellow")?,
+                expired: ClockWindow::make_theme(
+                    &colourmap, &themes, "expired", "black", "orange",
+                )?,
+                dim: ClockWindow::make_theme(&colourmap, &themes, "dim", "grey30", "black")?,
+                night_theme: ClockWindow::make_theme(
+                    &colourmap, &themes, "night", "grey20", "black",
+                )?,
+                shift: cfg.shift,
+                backlight: match &cfg.backlight {
+                    Some(directory) => match backlight::Backlight::new(directory) {
+                        Ok(b) => Some(b),
+                        Err(e) => {
+                            warn!("cannot use backlight {}: {}", directory, e);
+                            None
+                        }
+                    },
+                    None => None,
+                },
+                scheduled_brightness: None,
+                ambient: cfg.ambient.map(ambient::Ambient::new),
+                night: cfg.night,
+                awake_until: None,
+                power: power::Power::new(display, cfg.power),
+                drawn: HashMap::new(),
+                drawn_background: None,
+                output: cfg.output,
+                randr_event_base,
+                hidden,
+                width: window_width,
+                height: window_height,
+                layout_width: width,
+                layout_height: height,
                 wm_protocols: wm_protocols,
                 wm_delete_window: wm_delete_window,
                 input: input,
-            }
+                connection,
+            })
         }
     }
 
+    /// The first of the configured patterns that opens, or else the
+    /// built-in default, saying which was used when it was not the first
     fn make_font(
-        display: *mut x11::xlib::Display,
+        connection: &Rc<resource::Display>,
         screen_num: c_int,
-        name: &str,
-    ) -> *mut x11::xft::XftFont {
-        let font_str = CString::new(name).unwrap();
-        unsafe { xft::XftFontOpenName(display, screen_num, font_str.as_ptr() as *mut _) }
+        widget: &str,
+        patterns: Option<&Vec<String>>,
+        default: &str,
+    ) -> Result<resource::Font, StartupError> {
+        let mut failed = Vec::new();
+        let candidates = patterns.into_iter().flatten().map(|p| p.as_str());
+        for pattern in candidates.chain(std::iter::once(default)) {
+            match resource::Font::open(connection, screen_num, pattern) {
+                Some(font) => {
+                    if !failed.is_empty() {
+                        warn!(
+                            "{} font: cannot open {}, using {}",
+                            widget,
+                            failed.join(", "),
+                            pattern
+                        );
+                    }
+                    return Ok(font);
+                }
+                None => failed.push(pattern),
+            }
+        }
+        Err(StartupError::Font(format!(
+            "{} font: cannot open {}",
+            widget,
+            failed.join(", ")
+        )))
     }
 
     fn make_theme(
-        display: *mut x11::xlib::Display,
-        visual: *const x11::xlib::Visual,
-        colourmap: x11::xlib::Colormap,
-        theme: Option<&configure::StrMap>,
+        colourmap: &Rc<resource::Colourmap>,
+        themes: &configure::ThemeMap,
+        name: &str,
         foreground: &str,
         background: &str,
-    ) -> Theme {
-        let mut time_colour = foreground;
-        let mut day_colour = foreground;
-        let mut date_colour = foreground;
-        let mut weather_colour = foreground;
-        let mut background_colour = background;
-        match theme {
-            Some(t) => {
-                match t.get("time") {
-                    Some(c) => time_colour = c,
-                    None => (),
-                };
-                match t.get("day") {
-                    Some(c) => day_colour = c,
-                    None => (),
-                };
-                match t.get("date") {
-                    Some(c) => date_colour = c,
-                    None => (),
-                };
-                match t.get("weather") {
-                    Some(c) => weather_colour = c,
-                    None => (),
-                };
-                match t.get("background") {
-                    Some(c) => background_colour = c,
-                    None => (),
-                };
-            }
-            None => (),
+    ) -> Result<Theme, StartupError> {
+        let theme = themes.get(name);
+        let colour = |key: &str, default: &str| {
+            let names = theme.and_then(|t| t.get(key));
+            ClockWindow::make_colour(colourmap, name, key, names, default)
         };
-        Theme {
-            time: ClockWindow::make_colour(display, visual, colourmap, time_colour),
-            day: ClockWindow::make_colour(display, visual, colourmap, day_colour),
-            date: ClockWindow::make_colour(display, visual, colourmap, date_colour),
-            weather: ClockWindow::make_colour(display, visual, colourmap, weather_colour),
-            background: ClockWindow::make_colour(display, visual, colourmap, background_colour),
+
+        let mut brightness = None;
+        if let Some(b) = theme
+            .and_then(|t| t.get("brightness"))
+            .and_then(|b| b.first())
+        {
+            match b.parse::<u32>() {
+                Ok(b) if b <= 100 => brightness = Some(b),
+                _ => warn!("theme brightness {} is not a percentage", b),
+            }
         }
+        Ok(Theme {
+            time: colour("time", foreground)?,
+            day: colour("day", foreground)?,
+            date: colour("date", foreground)?,
+            weather: colour("weather", foreground)?,
+            progress: colour("progress", foreground)?,
+            calendar: colour("calendar", foreground)?,
+            today: colour("today", foreground)?,
+            zone: colour("zone", foreground)?,
+            timer: colour("timer", foreground)?,
+            background: colour("background", background)?,
+            brightness,
+        })
     }
 
+    /// The first of the configured colours that can be allocated, or
+    /// else the theme's default, saying which was used when it was not
+    /// the first
     fn make_colour(
-        display: *mut x11::xlib::Display,
-        visual: *const x11::xlib::Visual,
-        colourmap: x11::xlib::Colormap,
-        name: &str,
-    ) -> x11::xft::XftColor {
-        unsafe {
-            let mut colour: xft::XftColor = zeroed();
-            let c_str = CString::new(name).unwrap();
-            xft::XftColorAllocName(
-                display,
-                visual,
-                colourmap,
-                c_str.as_ptr() as *mut _,
-                &mut colour,
-            );
-            colour
+        colourmap: &Rc<resource::Colourmap>,
+        theme: &str,
+        key: &str,
+        names: Option<&Vec<String>>,
+        default: &str,
+    ) -> Result<Rc<resource::Colour>, StartupError> {
+        let mut failed = Vec::new();
+        let candidates = names.into_iter().flatten().map(|n| n.as_str());
+        for name in candidates.chain(std::iter::once(default)) {
+            match resource::Colour::alloc(colourmap, name) {
+                Some(colour) => {
+                    if !failed.is_empty() {
+                        warn!(
+                            "{} theme {} colour: cannot allocate {}, using {}",
+                            theme,
+                            key,
+                            failed.join(", "),
+                            name
+                        );
+                    }
+                    return Ok(Rc::new(colour));
+                }
+                None => failed.push(name),
+            }
         }
+        Err(StartupError::Colour(format!(
+            "{} theme {} colour: cannot allocate {}",
+            theme,
+            key,
+            failed.join(", ")
+        )))
     }
 
+    /// Ask for fullscreen before the window is first mapped
     fn fullscreen(&mut self) {
+        self.is_fullscreen = true;
         let net_wm_state_fullscreen_str = CString::new("_NET_WM_STATE_FULLSCREEN").unwrap();
         let net_wm_state_str = CString::new("_NET_WM_STATE").unwrap();
         unsafe {
@@ -367,29 +759,124 @@ impl ClockWindow {
         }
     }
 
+    /// Ask the window manager to enter or leave fullscreen, once the
+    /// window is mapped only a client message to the root will do
+    fn toggle_fullscreen(&mut self) {
+        let net_wm_state_fullscreen_str = CString::new("_NET_WM_STATE_FULLSCREEN").unwrap();
+        let net_wm_state_str = CString::new("_NET_WM_STATE").unwrap();
+        self.is_fullscreen = !self.is_fullscreen;
+        unsafe {
+            let mut message: xlib::XClientMessageEvent = zeroed();
+            message.type_ = xlib::ClientMessage;
+            message.window = self.window;
+            message.message_type =
+                xlib::XInternAtom(self.display, net_wm_state_str.as_ptr(), xlib::False);
+            message.format = 32;
+            // _NET_WM_STATE_REMOVE = 0, _NET_WM_STATE_ADD = 1
+            message.data.set_long(0, self.is_fullscreen as c_long);
+            message.data.set_long(
+                1,
+                xlib::XInternAtom(
+                    self.display,
+                    net_wm_state_fullscreen_str.as_ptr(),
+                    xlib::False,
+                ) as c_long,
+            );
+            message.data.set_long(3, 1); // from a normal application
+
+            let mut event = xlib::XEvent::from(message);
+            xlib::XSendEvent(
+                self.display,
+                xlib::XDefaultRootWindow(self.display),
+                xlib::False,
+                xlib::SubstructureRedirectMask | xlib::SubstructureNotifyMask,
+                &mut event,
+            );
+            xlib::XFlush(self.display);
+        }
+    }
+
+    /// Take the once a second readings: the light level, whose
+    /// smoothing assumes a steady rate, and the power schedule, which
+    /// asks the server for the DPMS state
+    fn sample(&mut self) {
+        if let Some(ambient) = &mut self.ambient {
+            ambient.update(self.input.light);
+        }
+
+        // an alarm or a finished countdown keeps the display on
+        let alerting =
+            self.input.alert.is_some() || self.input.countdown.is_some_and(|c| c.is_expired());
+        self.power.update(&Local::now(), self.last_touch, alerting);
+    }
+
     /// Display the window
     pub fn show(&mut self) {
         unsafe {
-            xlib::XMapWindow(self.display, self.window);
+            if !self.hidden {
+                xlib::XMapWindow(self.display, self.window);
+            }
 
             let dt = Local::now();
 
+            if let Some(timeout) = self.idle_timeout {
+                if self.page != 0 && self.last_touch.elapsed() >= timeout {
+                    self.page = 0;
+                }
+            }
+
             let t = dt.format("%H:%M:%S").to_string();
-            let time_len = t.len() as i32;
-            let time_str = CString::new(t).unwrap();
 
-            let d = &self.days[dt.weekday().num_days_from_sunday() as usize];
-            let day_len = d.len() as i32;
-            let day_str = CString::new(d.to_string()).unwrap();
+            let day = self.days[dt.weekday().num_days_from_sunday() as usize].clone();
 
             // let d = dt.format("%Y-%m-%d").to_string();
             let d = dt.format("%m-%d").to_string();
-            let date_len = d.len() as i32;
-            let date_str = CString::new(d).unwrap();
 
-            let (theme, weather, temperature) = {
-                let f = self.input.lock().unwrap();
-                let theme = if (*f).sync {
+            // only look for alarms once each minute
+            let minute = dt.date_naive().and_hms_opt(dt.hour(), dt.minute(), 0);
+            if self.alarm_checked != minute {
+                self.alarm_checked = minute;
+                self.check_alarms(&dt);
+            }
+
+            self.update_cursor();
+
+            let dark = self.ambient.as_ref().is_some_and(|a| a.is_dark());
+
+            let flash;
+            let (theme, weather, temperature, alert, timing, night) = {
+                let f = &self.input;
+
+                // a countdown shows in preference to the stopwatch
+                let (timing, expired) = match f.countdown {
+                    Some(c) => (Some(timer::format_duration(c.remaining())), c.is_expired()),
+                    None if f.stopwatch.is_active() => {
+                        (Some(timer::format_duration(f.stopwatch.elapsed())), false)
+                    }
+                    None => (None, false),
+                };
+
+                // alerts and finished countdowns still show at night
+                let night = if f.alert.is_none() && !expired {
+                    self.night_mode(&dt)
+                } else {
+                    None
+                };
+
+                let theme = if f.alert.is_some() {
+                    if dt.second().is_multiple_of(2) {
+                        &self.alert
+                    } else {
+                        flash = self.alert.inverted();
+                        &flash
+                    }
+                } else if expired {
+                    &self.expired
+                } else if night.is_some() {
+                    &self.night_theme
+                } else if self.dimmed || dark {
+                    &self.dim
+                } else if f.sync {
                     match dt.hour() {
                         0 | 1 | 2 | 3 | 4 | 5 => &self.early,
                         6 | 7 | 8 | 9 | 10 | 11 => &self.morning,
@@ -399,39 +886,72 @@ impl ClockWindow {
                 } else {
                     &self.unsync
                 };
-                (theme, (*f).weather.clone(), (*f).temperature.clone())
+                (
+                    theme,
+                    f.weather.clone(),
+                    f.temperature.clone(),
+                    f.alert.clone(),
+                    timing,
+                    night,
+                )
             };
-            //let weather_len = weather.len() as i32;
-
-            xft::XftDrawRect(self.draw, &theme.background, 0, 0, self.width, self.height);
+            let theme = theme.clone();
 
-            xft::XftDrawStringUtf8(
-                self.draw,
-                &theme.time,
-                self.time_font,
-                self.time_point.x,
-                self.time_point.y,
-                time_str.as_ptr() as *mut _,
-                time_len,
-            );
-            xft::XftDrawStringUtf8(
-                self.draw,
-                &theme.day,
-                self.day_font,
-                self.day_point.x,
-                self.day_point.y,
-                day_str.as_ptr() as *mut _,
-                day_len,
-            );
-            xft::XftDrawStringUtf8(
-                self.draw,
-                &theme.date,
-                self.date_font,
-                self.date_point.x,
-                self.date_point.y,
-                date_str.as_ptr() as *mut _,
-                date_len,
-            );
+            let scheduled = match night {
+                Some(configure::NightMode::Blank) => theme.brightness.or(Some(0)),
+                _ => theme.brightness,
+            };
+            let sensed = match (night, &self.ambient) {
+                (None, Some(ambient)) => ambient.brightness(),
+                _ => None,
+            };
+            self.set_brightness(scheduled, sensed);
+
+            let mut widgets = Vec::new();
+
+            if self.is_visible("time") {
+                widgets.push(match (&timing, self.timer_point) {
+                    (Some(text), None) => self.text_widget(
+                        "time",
+                        &theme.time,
+                        &self.timer_font,
+                        self.time_point,
+                        text,
+                    ),
+                    _ => {
+                        self.text_widget("time", &theme.time, &self.time_font, self.time_point, &t)
+                    }
+                });
+            }
+            if let (Some(text), Some(point)) = (&timing, self.timer_point) {
+                if self.is_visible("timer") {
+                    widgets.push(self.text_widget(
+                        "timer",
+                        &theme.timer,
+                        &self.timer_font,
+                        point,
+                        text,
+                    ));
+                }
+            }
+            if self.is_visible("day") {
+                widgets.push(self.text_widget(
+                    "day",
+                    &theme.day,
+                    &self.day_font,
+                    self.day_point,
+                    &day,
+                ));
+            }
+            if self.is_visible("date") {
+                widgets.push(self.text_widget(
+                    "date",
+                    &theme.date,
+                    &self.date_font,
+                    self.date_point,
+                    &d,
+                ));
+            }
 
             let w_count = weather.char_indices().count();
             let t_count = weather.char_indices().count();
@@ -448,59 +968,782 @@ impl ClockWindow {
             } else {
                 ww.push_str(&temperature);
             };
-            xft::XftDrawStringUtf8(
-                self.draw,
-                &theme.weather,
-                self.weather_font,
-                self.weather_point.x,
-                self.weather_point.y,
-                ww.as_ptr() as *mut _,
-                ww.len() as i32,
-            );
+            // an alert label replaces the weather, and is shown on
+            // every page
+            match alert {
+                Some(label) => widgets.push(self.text_widget(
+                    "alert",
+                    &theme.weather,
+                    &self.alert_font,
+                    self.alert_point,
+                    &label,
+                )),
+                None if self.is_visible("weather") => widgets.push(self.text_widget(
+                    "weather",
+                    &theme.weather,
+                    &self.weather_font,
+                    self.weather_point,
+                    &ww,
+                )),
+                None => (),
+            }
+
+            if let Some(progress) = self.progress {
+                if self.is_visible("progress") {
+                    let fraction = progress_fraction(&dt, progress.period);
+                    let point = self.place(self.progress_point);
+                    widgets.push(Widget {
+                        name: "progress".to_string(),
+                        key: format!(
+                            "{:x} {}",
+                            theme.progress.pixel,
+                            progress_step(&progress, fraction)
+                        ),
+                        area: Rect {
+                            x: point.x,
+                            y: point.y,
+                            width: progress.width.max(0) as c_uint,
+                            height: progress.height.max(0) as c_uint,
+                        },
+                        content: Content::Progress(point, progress, **theme.progress, fraction),
+                    });
+                }
+            }
+
+            if let Some(calendar) = self.calendar {
+                if self.is_visible("calendar") {
+                    let today = dt.date_naive();
+                    let point = self.place(self.calendar_point);
+                    widgets.push(Widget {
+                        name: "calendar".to_string(),
+                        key: format!(
+                            "{:x} {:x} {}",
+                            theme.calendar.pixel, theme.today.pixel, today
+                        ),
+                        area: self.calendar_area(point, &calendar),
+                        content: Content::Calendar(point, calendar, today),
+                    });
+                }
+            }
+
+            for z in self.zones.iter().filter(|z| self.is_visible(&z.zone.name)) {
+                let local = dt.with_timezone(&z.zone.zone);
+                let mut text = z.zone.label.clone();
+                if !text.is_empty() {
+                    text.push(' ');
+                }
+                text.push_str(&local.format(&z.zone.format).to_string());
+                widgets.push(self.text_widget(&z.zone.name, &theme.zone, &z.font, z.point, &text));
+            }
+
+            // at night just the hours and minutes, or nothing at all
+            match night {
+                Some(configure::NightMode::Minimal) => {
+                    let hm = dt.format("%H:%M").to_string();
+                    widgets.clear();
+                    widgets.push(self.text_widget(
+                        "time",
+                        &theme.time,
+                        &self.time_font,
+                        self.time_point,
+                        &hm,
+                    ));
+                }
+                Some(configure::NightMode::Blank) => widgets.clear(),
+                None => (),
+            }
+
+            if let Some(shift) = self.shift {
+                let (dx, dy) = self.bounded_shift(&widgets, shift_offset(&shift, &dt));
+                for w in widgets.iter_mut() {
+                    w.translate(dx, dy);
+                }
+            }
+
+            self.paint(&theme, widgets);
+        }
+    }
+
+    /// Forget what is on the pixmap so the next show draws everything
+    fn invalidate(&mut self) {
+        self.drawn.clear();
+        self.drawn_background = None;
+    }
+
+    /// Follow a change of window size with a pixmap to match, then lay
+    /// out and draw everything again
+    fn resize(&mut self, width: u32, height: u32) {
+        if (width == self.width && height == self.height) || width == 0 || height == 0 {
+            return;
+        }
+        let depth =
+            unsafe { xlib::XDefaultDepthOfScreen(xlib::XDefaultScreenOfDisplay(self.display)) };
+        let pixmap =
+            resource::Pixmap::new(&self.connection, self.window, width, height, depth as u32);
+        self.draw.change(&pixmap);
+        self.pixmap = pixmap;
+        self.width = width;
+        self.height = height;
+        self.invalidate();
+        self.show();
+    }
+
+    /// true for the XRandR events that say outputs have changed
+    fn is_output_change(&self, event: &xlib::XEvent) -> bool {
+        match self.randr_event_base {
+            Some(base) => {
+                let t = event.get_type();
+                t == base + xrandr::RRScreenChangeNotify || t == base + xrandr::RRNotify
+            }
+            None => false,
+        }
+    }
+
+    /// Keep to the chosen output when outputs change: move and resize
+    /// with it, hide while it is disconnected and come back after
+    fn follow_output(&mut self) {
+        let name = match &self.output {
+            Some(name) => name,
+            None => return,
+        };
+        unsafe {
+            let root = xlib::XDefaultRootWindow(self.display);
+            match output::find(self.display, root, name) {
+                Some(g) => {
+                    xlib::XMoveResizeWindow(self.display, self.window, g.x, g.y, g.width, g.height);
+                    if self.hidden {
+                        self.hidden = false;
+                        self.invalidate();
+                        self.show();
+                    }
+                }
+                None => {
+                    if !self.hidden {
+                        self.hidden = true;
+                        xlib::XUnmapWindow(self.display, self.window);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Move a configured position in proportion to the window's size
+    fn place(&self, point: configure::Point) -> configure::Point {
+        configure::Point {
+            x: (point.x as i64 * self.width as i64 / self.layout_width.max(1) as i64) as i32,
+            y: (point.y as i64 * self.height as i64 / self.layout_height.max(1) as i64) as i32,
+        }
+    }
+
+    /// Follow the light sensor's backlight level, or else the theme's;
+    /// a level set through the socket holds until the theme's changes
+    fn set_brightness(&mut self, scheduled: Option<u32>, sensed: Option<u32>) {
+        if scheduled != self.scheduled_brightness {
+            self.scheduled_brightness = scheduled;
+            self.input.brightness = None;
+        }
+        if let (Some(backlight), Some(level)) = (
+            &mut self.backlight,
+            self.input.brightness.or(sensed).or(scheduled),
+        ) {
+            if let Err(e) = backlight.set(level) {
+                warn!("cannot set backlight: {}", e);
+            }
+        }
+    }
+
+    /// Limit a shift so it moves nothing further off the window than
+    /// it was already
+    fn bounded_shift(&self, widgets: &[Widget], (dx, dy): (i32, i32)) -> (i32, i32) {
+        if widgets.is_empty() {
+            return (0, 0);
+        }
+        let left = widgets.iter().map(|w| w.area.x).min().unwrap();
+        let top = widgets.iter().map(|w| w.area.y).min().unwrap();
+        let right = widgets
+            .iter()
+            .map(|w| w.area.x + w.area.width as i32)
+            .max()
+            .unwrap();
+        let bottom = widgets
+            .iter()
+            .map(|w| w.area.y + w.area.height as i32)
+            .max()
+            .unwrap();
+
+        let dx = dx
+            .min((self.width as i32 - right).max(0))
+            .max((-left).min(0));
+        let dy = dy
+            .min((self.height as i32 - bottom).max(0))
+            .max((-top).min(0));
+        (dx, dy)
+    }
+
+    /// Draw the widgets that differ from the last call and copy just
+    /// their areas to the window; clearing an area also redraws any
+    /// unchanged widget that overlaps it
+    fn paint(&mut self, theme: &Theme, widgets: Vec<Widget>) {
+        let full = self.drawn_background != Some(theme.background.pixel);
+
+        let mut redraw: Vec<bool> = widgets
+            .iter()
+            .map(|w| {
+                full || !matches!(self.drawn.get(&w.name),
+                        Some((key, area)) if *key == w.key && *area == w.area)
+            })
+            .collect();
+
+        let mut dirty = Vec::new();
+        if full {
+            dirty.push(Rect {
+                x: 0,
+                y: 0,
+                width: self.width,
+                height: self.height,
+            });
+        } else {
+            // where changed or vanished widgets were, and where the
+            // changed ones go now
+            for (name, (key, area)) in &self.drawn {
+                match widgets.iter().find(|w| &w.name == name) {
+                    Some(w) if w.key == *key && w.area == *area => (),
+                    _ => dirty.push(*area),
+                }
+            }
+            for (w, r) in widgets.iter().zip(&redraw) {
+                if *r {
+                    dirty.push(w.area);
+                }
+            }
+
+            loop {
+                let mut more = false;
+                for (w, r) in widgets.iter().zip(redraw.iter_mut()) {
+                    if !*r && dirty.iter().any(|d| d.intersects(&w.area)) {
+                        *r = true;
+                        dirty.push(w.area);
+                        more = true;
+                    }
+                }
+                if !more {
+                    break;
+                }
+            }
+        }
 
+        unsafe {
+            for d in &dirty {
+                xft::XftDrawRect(
+                    self.draw.raw(),
+                    &**theme.background,
+                    d.x,
+                    d.y,
+                    d.width,
+                    d.height,
+                );
+            }
+        }
+        for (w, r) in widgets.iter().zip(&redraw) {
+            if *r {
+                self.render(theme, &w.content);
+            }
+        }
+        for d in &dirty {
+            self.copy_area(d);
+        }
+
+        self.drawn = widgets
+            .into_iter()
+            .map(|w| (w.name, (w.key, w.area)))
+            .collect();
+        self.drawn_background = Some(theme.background.pixel);
+    }
+
+    fn render(&self, theme: &Theme, content: &Content) {
+        match content {
+            Content::Text(colour, font, point, text) => {
+                self.draw_string(colour, *font, point.x, point.y, text)
+            }
+            Content::Progress(point, progress, colour, fraction) => {
+                self.draw_progress(*point, progress, colour, *fraction)
+            }
+            Content::Calendar(point, calendar, today) => {
+                self.draw_calendar(*point, calendar, theme, *today)
+            }
+        }
+    }
+
+    /// Copy part of the pixmap to the window
+    fn copy_area(&self, area: &Rect) {
+        unsafe {
             xlib::XCopyArea(
                 self.display,
-                self.pixmap,
+                self.pixmap.raw(),
                 self.window,
-                self.gc,
-                0,
-                0,
-                self.width,
-                self.height,
-                0,
-                0,
+                self.gc.raw(),
+                area.x,
+                area.y,
+                area.width,
+                area.height,
+                area.x,
+                area.y,
+            );
+        }
+    }
+
+    fn text_widget(
+        &self,
+        name: &str,
+        colour: &x11::xft::XftColor,
+        font: &resource::Font,
+        point: configure::Point,
+        text: &str,
+    ) -> Widget {
+        let font = font.raw();
+        let point = self.place(point);
+        Widget {
+            name: name.to_string(),
+            key: format!("{:x} {:p} {}", colour.pixel, font, text),
+            area: self.text_area(font, point.x, point.y, text),
+            content: Content::Text(*colour, font, point, text.to_string()),
+        }
+    }
+
+    /// true if the named widget is on the current page
+    fn is_visible(&self, name: &str) -> bool {
+        match self.pages.get(self.page) {
+            Some(page) => page.iter().any(|n| n == name),
+            None => true,
+        }
+    }
+
+    /// Move to the next page, wrapping round to the first
+    pub fn next_page(&mut self) {
+        if !self.pages.is_empty() {
+            self.page = (self.page + 1) % self.pages.len();
+        }
+    }
+
+    /// Move to the previous page, wrapping round to the last
+    pub fn previous_page(&mut self) {
+        if !self.pages.is_empty() {
+            self.page = (self.page + self.pages.len() - 1) % self.pages.len();
+        }
+    }
+
+    pub fn first_page(&mut self) {
+        self.page = 0;
+    }
+
+    /// The night mode if it is night and no touch has woken the display
+    fn night_mode(&self, dt: &DateTime<Local>) -> Option<configure::NightMode> {
+        let night = self.night?;
+        if self
+            .awake_until
+            .is_some_and(|t| std::time::Instant::now() < t)
+        {
+            return None;
+        }
+
+        let now = dt.hour() * 60 + dt.minute();
+        let start = night.start.0 * 60 + night.start.1;
+        let stop = night.stop.0 * 60 + night.stop.1;
+        let is_night = if start <= stop {
+            start <= now && now < stop
+        } else {
+            now >= start || now < stop
+        };
+        if is_night {
+            Some(night.mode)
+        } else {
+            None
+        }
+    }
+
+    /// Wake the display for a while if it is showing the night mode;
+    /// true if it was, so the touch that woke it does nothing else
+    pub fn wake(&mut self) -> bool {
+        let night = match self.night {
+            Some(night) => night,
+            None => return false,
+        };
+        if self.night_mode(&Local::now()).is_none() {
+            return false;
+        }
+        self.awake_until =
+            Some(std::time::Instant::now() + std::time::Duration::from_secs(night.wake as u64));
+        self.show();
+        true
+    }
+
+    /// Restart the idle time before returning to the first page
+    pub fn touched(&mut self) {
+        self.last_touch = std::time::Instant::now();
+        self.update_cursor();
+    }
+
+    /// Hide the cursor if configured to (by default when fullscreen),
+    /// showing it for a while after it moves
+    fn update_cursor(&mut self) {
+        let moved = self
+            .cursor_timeout
+            .is_some_and(|timeout| self.last_touch.elapsed() < timeout);
+        let hidden = self.hide_cursor.unwrap_or(self.is_fullscreen) && !moved;
+        if hidden == self.cursor_hidden {
+            return;
+        }
+        self.cursor_hidden = hidden;
+        unsafe {
+            if hidden {
+                xlib::XDefineCursor(self.display, self.window, self.blank_cursor.raw());
+            } else {
+                xlib::XUndefineCursor(self.display, self.window);
+            }
+            xlib::XFlush(self.display);
+        }
+    }
+
+    /// Dismiss an alert or a finished countdown
+    pub fn dismiss(&mut self) {
+        self.input.dismiss();
+    }
+
+    /// Start or stop a stopwatch that is on display when a tap has
+    /// nothing to dismiss, true if it did
+    pub fn tap_stopwatch(&mut self) -> bool {
+        let f = &mut self.input;
+        if f.alert.is_some() || f.countdown.is_some() || !f.stopwatch.is_active() {
+            return false;
+        }
+        f.stopwatch.toggle();
+        true
+    }
+
+    pub fn key_action(&self, sym: xlib::KeySym) -> Option<action::Action> {
+        self.keys.get(&sym).cloned()
+    }
+
+    pub fn button_action(&self, button: u32) -> Option<action::Action> {
+        self.buttons.get(&button).cloned()
+    }
+
+    pub fn gesture_action(&self, gesture: gesture::Gesture) -> Option<action::Action> {
+        self.gestures.get(&gesture).cloned()
+    }
+
+    /// Carry out an action and redraw, true if the event loop should
+    /// stop to quit or reload
+    pub fn perform(&mut self, action: &action::Action) -> bool {
+        match action {
+            action::Action::Quit | action::Action::Reload => return true,
+            action::Action::NextPage => self.next_page(),
+            action::Action::PreviousPage => self.previous_page(),
+            action::Action::FirstPage => self.first_page(),
+            action::Action::Fullscreen => self.toggle_fullscreen(),
+            action::Action::Dim => self.dimmed = !self.dimmed,
+            action::Action::Dismiss => self.dismiss(),
+            action::Action::Run(command) => action::spawn(command, &[]),
+        }
+        self.show();
+        false
+    }
+
+    /// Raise an alert for any alarm due in the current minute, socket
+    /// alarms only go off once
+    fn check_alarms(&mut self, dt: &DateTime<Local>) {
+        let f = &mut self.input;
+
+        let mut label = self
+            .alarms
+            .iter()
+            .find(|a| a.is_due(dt))
+            .map(|a| a.label.clone());
+        if let Some(i) = f.alarms.iter().position(|a| a.is_due(dt)) {
+            label = Some(f.alarms.remove(i).label);
+        }
+
+        if let Some(label) = label {
+            if let Some(command) = &self.alarm_command {
+                action::spawn(command, &[("ALARM_LABEL", &label)]);
+            }
+            f.alert = Some(label);
+        }
+    }
+
+    /// Draw the progress widget at |point| with |fraction| of it filled
+    fn draw_progress(
+        &self,
+        point: configure::Point,
+        progress: &configure::Progress,
+        colour: &x11::xft::XftColor,
+        fraction: f64,
+    ) {
+        let x = point.x;
+        let y = point.y;
+        unsafe {
+            match progress.style {
+                configure::ProgressStyle::Bar => {
+                    let filled = progress_step(progress, fraction) as c_uint;
+                    xft::XftDrawRect(
+                        self.draw.raw(),
+                        colour,
+                        x,
+                        y,
+                        filled,
+                        progress.height as c_uint,
+                    );
+                }
+                configure::ProgressStyle::Ring => {
+                    // the line is centred on the arc, so inset by half
+                    // its thickness to keep within the bounding box
+                    let thickness = progress.thickness;
+                    let diameter = progress.width.min(progress.height) - thickness;
+                    if diameter <= 0 {
+                        return;
+                    }
+
+                    // X angles are in 1/64 degree, anticlockwise from
+                    // three o'clock; start at twelve and run clockwise
+                    let extent = -progress_step(progress, fraction);
+
+                    xlib::XSetForeground(self.display, self.gc.raw(), colour.pixel);
+                    xlib::XSetLineAttributes(
+                        self.display,
+                        self.gc.raw(),
+                        thickness as c_uint,
+                        xlib::LineSolid,
+                        xlib::CapButt,
+                        xlib::JoinMiter,
+                    );
+                    xlib::XDrawArc(
+                        self.display,
+                        self.pixmap.raw(),
+                        self.gc.raw(),
+                        x + thickness / 2,
+                        y + thickness / 2,
+                        diameter as c_uint,
+                        diameter as c_uint,
+                        90 * 64,
+                        extent,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Draw the month containing |today| as a grid of day numbers
+    /// under a header of the configured day names
+    fn draw_calendar(
+        &self,
+        point: configure::Point,
+        calendar: &configure::Calendar,
+        theme: &Theme,
+        today: NaiveDate,
+    ) {
+        let font = self.calendar_font.raw();
+        let ascent = unsafe { (*font).ascent };
+        let (column, row) = self.calendar_grid(calendar);
+
+        let x = point.x;
+        let y = point.y;
+
+        for i in 0..7 {
+            let name = &self.days[((calendar.first_day + i) % 7) as usize];
+            let offset = column - CALENDAR_PADDING - self.text_width(font, name);
+            self.draw_string(
+                &theme.calendar,
+                font,
+                x + i as i32 * column + offset,
+                y,
+                name,
+            );
+        }
+
+        let first = today.with_day(1).unwrap();
+        let skip = (first.weekday().num_days_from_sunday() + 7 - calendar.first_day) % 7;
+
+        for day in 1..=days_in_month(first) {
+            let cell = skip + day - 1;
+            let cx = x + (cell % 7) as i32 * column;
+            let cy = y + (cell / 7 + 1) as i32 * row;
+
+            let text = day.to_string();
+            let offset = column - CALENDAR_PADDING - self.text_width(font, &text);
+            if day == today.day() {
+                unsafe {
+                    xft::XftDrawRect(
+                        self.draw.raw(),
+                        &**theme.today,
+                        cx,
+                        cy - ascent - CALENDAR_PADDING / 2,
+                        (column - CALENDAR_PADDING / 2) as c_uint,
+                        row as c_uint,
+                    );
+                }
+                self.draw_string(&theme.background, font, cx + offset, cy, &text);
+            } else {
+                self.draw_string(&theme.calendar, font, cx + offset, cy, &text);
+            }
+        }
+    }
+
+    /// Column width and row height of the calendar, from the font
+    /// unless configured
+    fn calendar_grid(&self, calendar: &configure::Calendar) -> (i32, i32) {
+        let font = self.calendar_font.raw();
+        let line_height = unsafe { (*font).height };
+
+        let column = if calendar.column > 0 {
+            calendar.column
+        } else {
+            let widest = self
+                .days
+                .iter()
+                .map(|d| self.text_width(font, d))
+                .chain(std::iter::once(self.text_width(font, "30")))
+                .max()
+                .unwrap_or(0);
+            widest + CALENDAR_PADDING
+        };
+        let row = if calendar.row > 0 {
+            calendar.row
+        } else {
+            line_height + CALENDAR_PADDING
+        };
+        (column, row)
+    }
+
+    /// Room for the header and six weeks, whatever the month
+    fn calendar_area(&self, point: configure::Point, calendar: &configure::Calendar) -> Rect {
+        let (ascent, line_height) = unsafe {
+            let font = self.calendar_font.raw();
+            ((*font).ascent, (*font).height)
+        };
+        let (column, row) = self.calendar_grid(calendar);
+        Rect {
+            x: point.x,
+            y: point.y - ascent - CALENDAR_PADDING,
+            width: (7 * column).max(0) as c_uint,
+            height: (7 * row + line_height).max(0) as c_uint,
+        }
+    }
+
+    /// The horizontal advance of |text| rendered in |font|
+    fn text_width(&self, font: *mut x11::xft::XftFont, text: &str) -> i32 {
+        self.text_extents(font, text).xOff as i32
+    }
+
+    fn text_extents(&self, font: *mut x11::xft::XftFont, text: &str) -> x11::xrender::XGlyphInfo {
+        unsafe {
+            let mut extents: x11::xrender::XGlyphInfo = zeroed();
+            xft::XftTextExtentsUtf8(
+                self.display,
+                font,
+                text.as_ptr(),
+                text.len() as c_int,
+                &mut extents,
+            );
+            extents
+        }
+    }
+
+    /// The box covering both the ink and the line of |text| drawn at
+    /// (x, y) in |font|
+    fn text_area(&self, font: *mut x11::xft::XftFont, x: i32, y: i32, text: &str) -> Rect {
+        let e = self.text_extents(font, text);
+        let (ascent, descent) = unsafe { ((*font).ascent, (*font).descent) };
+
+        let left = x.min(x - e.x as i32);
+        let right = (x + e.xOff as i32).max(x - e.x as i32 + e.width as i32);
+        let top = (y - ascent).min(y - e.y as i32);
+        let bottom = (y + descent).max(y - e.y as i32 + e.height as i32);
+        Rect {
+            x: left,
+            y: top,
+            width: (right - left).max(0) as c_uint,
+            height: (bottom - top).max(0) as c_uint,
+        }
+    }
+
+    fn draw_string(
+        &self,
+        colour: &x11::xft::XftColor,
+        font: *mut x11::xft::XftFont,
+        x: i32,
+        y: i32,
+        text: &str,
+    ) {
+        unsafe {
+            xft::XftDrawStringUtf8(
+                self.draw.raw(),
+                colour,
+                font,
+                x,
+                y,
+                text.as_ptr(),
+                text.len() as c_int,
             );
         }
     }
 
     /// Process events for the window. Window close events are handled automatically,
-    /// other events are passed on to |event_handler|
-    pub fn run_event_loop<EventHandler>(&mut self, mut event_handler: EventHandler)
+    /// other events are passed on to |event_handler| which may return an
+    /// action to perform; the X connection, the once a second tick and
+    /// the socket are all waited on together, and socket commands redraw
+    /// straight away; SIGTERM or SIGINT stops the loop as Quit between
+    /// redraws. Returns the action that stopped the loop, Quit or
+    /// Reload, or an error if the tick timer cannot be created
+    pub fn run_event_loop<EventHandler>(
+        &mut self,
+        server: &mut socket::Server,
+        signals: &mut signal::Signals,
+        mut event_handler: EventHandler,
+    ) -> Result<action::Action, StartupError>
     where
-        EventHandler: FnMut(&xlib::XEvent) -> bool,
+        EventHandler: FnMut(&mut ClockWindow, &xlib::XEvent) -> Option<action::Action>,
     {
         let x11_fd = unsafe { xlib::XConnectionNumber(self.display) };
 
-        let mut event: xlib::XEvent = unsafe { zeroed() };
-        let mut in_fds: libc::fd_set = unsafe { zeroed() };
+        // redraw on each whole second however busy the X connection is
+        let ticker = tick::Ticker::new().map_err(|e| StartupError::System("tick timer", e))?;
+        let tick_fd = ticker.fd();
 
-        'event_loop: loop {
-            let mut tv = libc::timeval {
-                tv_usec: 0, // 500_000,
-                tv_sec: 1,  // 0,
-            };
+        let mut event: xlib::XEvent = unsafe { zeroed() };
 
-            // Create a File Description Set containing x11_fd
-            unsafe {
-                libc::FD_ZERO(&mut in_fds);
-                libc::FD_SET(x11_fd, &mut in_fds);
-            }
+        loop {
+            // the X connection, the ticker and signals, then the socket's entries
+            let mut fds = vec![
+                libc::pollfd {
+                    fd: x11_fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+                libc::pollfd {
+                    fd: tick_fd,
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+                libc::pollfd {
+                    fd: signals.fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+            ];
+            fds.extend(server.pollfds());
 
-            let status =
-                unsafe { libc::select(x11_fd + 1, &mut in_fds, null_mut(), null_mut(), &mut tv) };
-            if status == 0 {
-                // println!("timer tick");
-                self.show();
+            let status = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) };
+            if status > 0 {
+                if fds[2].revents != 0 && signals.read() {
+                    return Ok(action::Action::Quit);
+                }
+                if fds[1].revents != 0 && ticker.read() {
+                    self.sample();
+                    self.show();
+                }
+                if server.service(&fds[3..], &mut self.input) {
+                    self.show();
+                }
             }
 
             loop {
@@ -510,7 +1753,25 @@ impl ClockWindow {
                 }
 
                 unsafe { xlib::XNextEvent(self.display, &mut event) };
+                if self.is_output_change(&event) {
+                    unsafe { xrandr::XRRUpdateConfiguration(&mut event) };
+                    self.follow_output();
+                    continue;
+                }
                 match event.get_type() {
+                    xlib::ConfigureNotify => {
+                        let configure: xlib::XConfigureEvent = From::from(event);
+                        self.resize(configure.width as u32, configure.height as u32);
+                    }
+                    xlib::Expose => {
+                        let expose: xlib::XExposeEvent = From::from(event);
+                        self.copy_area(&Rect {
+                            x: expose.x,
+                            y: expose.y,
+                            width: expose.width as c_uint,
+                            height: expose.height as c_uint,
+                        });
+                    }
                     xlib::ClientMessage => {
                         let xclient: xlib::XClientMessageEvent = From::from(event);
 
@@ -520,47 +1781,122 @@ impl ClockWindow {
 
                             // WM_DELETE_WINDOW (close event)
                             if protocol == self.wm_delete_window {
-                                break 'event_loop;
+                                return Ok(action::Action::Quit);
                             }
                         }
                     }
                     _ => {
-                        if !event_handler(&event) {
-                            break 'event_loop;
+                        if let Some(action) = event_handler(self, &event) {
+                            if self.perform(&action) {
+                                return Ok(action);
+                            }
                         }
                     }
                 }
             }
+
+            let actions: Vec<action::Action> = self.input.actions.drain(..).collect();
+            for action in actions {
+                if self.perform(&action) {
+                    return Ok(action);
+                }
+            }
+        }
+    }
+}
+
+// how far through the current period the time is, from 0.0 up to 1.0
+fn progress_fraction(dt: &DateTime<Local>, period: configure::Period) -> f64 {
+    let seconds = dt.second() as f64 + dt.nanosecond().min(999_999_999) as f64 / 1e9;
+    match period {
+        configure::Period::Minute => seconds / 60.0,
+        configure::Period::Hour => (dt.minute() as f64 * 60.0 + seconds) / 3600.0,
+        configure::Period::Day => {
+            (dt.num_seconds_from_midnight() as f64 + seconds.fract()) / 86400.0
         }
     }
 }
 
+// the offset for the shift period containing |dt|: round a circle of
+// |range| pixels in eight steps, or a pseudo-random point within it
+fn shift_offset(shift: &configure::Shift, dt: &DateTime<Local>) -> (i32, i32) {
+    let step = dt.timestamp() / 60 / shift.interval.max(1) as i64;
+    let range = shift.range.max(0) as f64;
+    let (radius, angle) = match shift.mode {
+        configure::ShiftMode::Orbit => (range, step as f64 * std::f64::consts::PI / 4.0),
+        configure::ShiftMode::Random => {
+            // the same step always gives the same point
+            let mut n = step as u64 ^ 0x9e37_79b9_7f4a_7c15;
+            n ^= n >> 33;
+            n = n.wrapping_mul(0xff51_afd7_ed55_8ccd);
+            n ^= n >> 33;
+            let r = (n & 0xffff) as f64 / 65535.0;
+            let a = (n >> 16 & 0xffff) as f64 / 65535.0;
+            (range * r.sqrt(), a * 2.0 * std::f64::consts::PI)
+        }
+    };
+    (
+        (radius * angle.cos()).round() as i32,
+        (radius * angle.sin()).round() as i32,
+    )
+}
+
+// the filled width of a bar or the arc of a ring in 1/64 degree, so a
+// redraw is only needed when this changes
+fn progress_step(progress: &configure::Progress, fraction: f64) -> i32 {
+    match progress.style {
+        configure::ProgressStyle::Bar => (progress.width as f64 * fraction) as i32,
+        configure::ProgressStyle::Ring => (fraction * 360.0 * 64.0) as i32,
+    }
+}
+
+// number of days in the month starting on |first|
+fn days_in_month(first: NaiveDate) -> u32 {
+    let next = if first.month() == 12 {
+        NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)
+    };
+    next.unwrap().signed_duration_since(first).num_days() as u32
+}
+
 impl Drop for ClockWindow {
-    /// Destroys the window and disconnects from the display
+    /// Destroys the window; the fonts, colours and drawing resources
+    /// free themselves, and the display closes after the last of them
     fn drop(&mut self) {
         unsafe {
             xlib::XDestroyWindow(self.display, self.window);
-            xlib::XCloseDisplay(self.display);
         }
     }
 }
 
 // entry point
 fn main() {
+    let code = match run() {
+        Ok(code) => code,
+        Err(e) => {
+            error!("{}", e);
+            e.exit_code()
+        }
+    };
+    std::process::exit(code);
+}
+
+// the clock itself, returning the exit status once it stops
+fn run() -> Result<i32, StartupError> {
     // The YAML file is found relative to the current file, similar to how modules are found
     let yaml = load_yaml!("cli.yaml");
     let matches = App::from_yaml(yaml).get_matches();
 
-    let debug = matches.is_present("debug");
-
-    if debug {
-        match matches.occurrences_of("verbose") {
-            0 => println!("verbose mode is off"),
-            1 => println!("verbose mode is low"),
-            2 => println!("verbose mode is on"),
-            3 | _ => println!("maximum verbosity"),
-        }
+    let mut level = logger::level(matches.occurrences_of("verbose"));
+    if matches.is_present("debug") {
+        level = level.max(log::LevelFilter::Debug);
     }
+    logger::init(
+        level,
+        matches.value_of("log-file"),
+        matches.is_present("syslog"),
+    );
 
     let fullscreen = matches.is_present("fullscreen");
 
@@ -574,43 +1910,117 @@ fn main() {
                 config.push(DEFAULT_CONFIG_DIR);
                 config.push(DEFAULT_CONFIG_FILE);
             }
-            None => panic!("cannot get home directory"),
+            None => return Err(StartupError::NoConfigDirectory),
         },
     };
 
     // end of options processing
+    // SIGTERM and SIGINT are taken by the event loop, so block them
+    // before anything else starts
+    let mut signals =
+        signal::Signals::new().map_err(|e| StartupError::System("signal handling", e))?;
+
     // read configurartion file
-    let cfg = configure::read(&config, debug).expect("error in config file");
+    let mut cfg = configure::read(&config).map_err(|e| StartupError::Config(config.clone(), e))?;
+
+    // one clock per socket, so a second one does not take it over
+    // unless asked to
+    let lock_path = format!("{}.lock", cfg.socket);
+    let locking = |e| StartupError::Lock(lock_path.clone(), e);
+    let _lock = match lock::Lock::take(&lock_path).map_err(locking)? {
+        Some(lock) => lock,
+        None if matches.is_present("replace") => {
+            info!("asking the clock on {} to quit", cfg.socket);
+            if let Err(e) = socket::send(&cfg.socket, "quit") {
+                warn!("cannot send quit to {}: {}", cfg.socket, e);
+            }
+            lock::Lock::wait(&lock_path, REPLACE_TIMEOUT)
+                .map_err(locking)?
+                .ok_or_else(|| StartupError::Running(cfg.socket.clone(), true))?
+        }
+        None => return Err(StartupError::Running(cfg.socket.clone(), false)),
+    };
 
     // setup socket
-    let sync_flag = socket::setup(&cfg.socket, debug).unwrap();
+    let mut server = socket::Server::new(&cfg.socket)
+        .map_err(|e| StartupError::Socket(cfg.socket.clone(), e))?;
+    let mut input = socket::Input::new();
+
+    // the window is rebuilt from scratch on each reload; the socket
+    // and its state carry on
+    'reload: loop {
+        // setup window
+        let width = if cfg.width > 0 {
+            cfg.width as u32
+        } else {
+            DEFAULT_WIDTH
+        };
+        let height = if cfg.height > 0 {
+            cfg.height as u32
+        } else {
+            DEFAULT_HEIGHT
+        };
 
-    // setup window
-    let width = if cfg.width > 0 {
-        cfg.width as u32
-    } else {
-        DEFAULT_WIDTH
-    };
-    let height = if cfg.height > 0 {
-        cfg.height as u32
-    } else {
-        DEFAULT_HEIGHT
-    };
+        if let Some(name) = matches.value_of("output") {
+            cfg.output = Some(name.to_string());
+        }
 
-    let mut clock_window = ClockWindow::new(
-        TITLE,
-        width,
-        height,
-        cfg.days,
-        cfg.fonts,
-        cfg.coordinates,
-        cfg.themes,
-        sync_flag,
-    );
-    if fullscreen {
-        clock_window.fullscreen();
+        let mut clock_window = ClockWindow::new(TITLE, width, height, cfg, input)?;
+        if fullscreen {
+            clock_window.fullscreen();
+        }
+
+        select_input(&clock_window);
+
+        // Show window
+        clock_window.show();
+
+        // Main loop
+        let mut gestures = gesture::Recogniser::new();
+        loop {
+            let action =
+                clock_window.run_event_loop(&mut server, &mut signals, |clock_window, event| {
+                    handle_event(clock_window, event, &mut gestures)
+                })?;
+            match action {
+                action::Action::Reload => match configure::read(&config) {
+                    Ok(c) => {
+                        cfg = c;
+                        input = std::mem::take(&mut clock_window.input);
+                        continue 'reload;
+                    }
+                    Err(e) => error!("error in config file, not reloaded: {}", e),
+                },
+                _ => break 'reload,
+            }
+        }
     }
 
+    Ok(signals.exit_code())
+}
+
+// select key, button and motion events: through XInput2 where the
+// server has it, otherwise (e.g. some VNC servers) as core events
+fn select_input(clock_window: &ClockWindow) {
+    if let Err(reason) = select_xinput2(clock_window) {
+        warn!("{}, using core input events", reason);
+        unsafe {
+            xlib::XSelectInput(
+                clock_window.display,
+                clock_window.window,
+                WINDOW_EVENTS
+                    | xlib::KeyPressMask
+                    | xlib::KeyReleaseMask
+                    | xlib::ButtonPressMask
+                    | xlib::ButtonReleaseMask
+                    | xlib::PointerMotionMask,
+            );
+        }
+    }
+}
+
+// select the XInput2 key, button and motion events
+fn select_xinput2(clock_window: &ClockWindow) -> Result<(), String> {
     // query XInput support
     let mut opcode: c_int = 0;
     let mut event: c_int = 0;
@@ -626,7 +2036,7 @@ fn main() {
         )
     };
     if xinput_available == xlib::False {
-        panic!("XInput not available")
+        return Err("XInput not available".to_string());
     }
 
     let mut xinput_major_ver = xinput2::XI_2_Major;
@@ -639,14 +2049,12 @@ fn main() {
         )
     } != xlib::Success as c_int
     {
-        panic!("XInput2 not available");
-    }
-    if debug {
-        println!(
-            "XI version available {}.{}",
-            xinput_major_ver, xinput_minor_ver
-        );
+        return Err("XInput2 not available".to_string());
     }
+    info!(
+        "XI version available {}.{}",
+        xinput_major_ver, xinput_minor_ver
+    );
 
     // init XInput events
     let mut mask: [c_uchar; 1] = [0];
@@ -674,71 +2082,148 @@ fn main() {
             1,
         )
     } {
-        status if status as u8 == xlib::Success => (),
-        err => panic!("Failed to select events {:?}", err),
+        status if status as u8 == xlib::Success => Ok(()),
+        err => Err(format!("failed to select XInput2 events {:?}", err)),
     }
+}
 
-    // Show window
-    clock_window.show();
-
-    // Main loop
+// map key, button and touch events to actions through the bindings
+fn handle_event(
+    clock_window: &mut ClockWindow,
+    event: &xlib::XEvent,
+    gestures: &mut gesture::Recogniser,
+) -> Option<action::Action> {
     let display = clock_window.display;
-
-    // event callback can return false to exit
-    clock_window.run_event_loop(|event| match event.get_type() {
+    match event.get_type() {
         xlib::GenericEvent => {
             let mut cookie: xlib::XGenericEventCookie = From::from(*event);
             if unsafe { xlib::XGetEventData(display, &mut cookie) } != xlib::True {
-                if debug {
-                    println!("Failed to retrieve event data");
-                }
-                return true;
+                debug!("failed to retrieve event data");
+                return None;
             }
-            let mut can_continue = true;
+            let mut action = None;
             match cookie.evtype {
                 xinput2::XI_KeyPress | xinput2::XI_KeyRelease => {
                     let event_data: &xinput2::XIDeviceEvent = unsafe { transmute(cookie.data) };
                     if cookie.evtype == xinput2::XI_KeyPress {
                         if event_data.flags & xinput2::XIKeyRepeat == 0 {
-                            println!("Key {} pressed", event_data.detail);
+                            action = key_pressed(clock_window, event_data.detail as u32);
                         }
                     } else {
-                        println!("Key {} released", event_data.detail);
+                        debug!("Key {} released", event_data.detail);
                     }
-
-                    const KEY_ESCAPE: xlib::KeySym = x11::keysym::XK_Escape as xlib::KeySym;
-
-                    let sym = unsafe {
-                        xlib::XkbKeycodeToKeysym(event_data.display, event_data.detail as u8, 0, 0)
-                    };
-
-                    can_continue = sym != KEY_ESCAPE; // exit if Esc key pressed
                 }
                 xinput2::XI_ButtonPress | xinput2::XI_ButtonRelease => {
                     let event_data: &xinput2::XIDeviceEvent = unsafe { transmute(cookie.data) };
-                    if cookie.evtype == xinput2::XI_ButtonPress {
-                        println!("Button {} pressed", event_data.detail);
+                    let (x, y) = (event_data.event_x, event_data.event_y);
+                    let time = event_data.time as u64;
+                    let button = event_data.detail as u32;
+                    action = if cookie.evtype == xinput2::XI_ButtonPress {
+                        button_pressed(clock_window, gestures, button, x, y, time)
                     } else {
-                        println!("Button {} released", event_data.detail);
-                    }
+                        button_released(clock_window, gestures, button, x, y, time)
+                    };
+                    clock_window.touched();
                 }
                 xinput2::XI_Motion => {
-                    if debug {
-                        println!("motion event");
-                    }
-                }
-                _ => {
-                    println!("what event");
+                    trace!("motion event");
+                    clock_window.touched();
                 }
+                evtype => trace!("XInput2 event {}", evtype),
             }
             unsafe { xlib::XFreeEventData(display, &mut cookie) };
-            can_continue
+            action
+        }
+
+        // the same from core events, when there is no XInput2
+        xlib::KeyPress => {
+            let key: xlib::XKeyEvent = From::from(*event);
+            key_pressed(clock_window, key.keycode)
         }
-        _ => {
-            if debug {
-                println!("some other event");
+        xlib::KeyRelease => {
+            // auto-repeat arrives as a release and a press with the same
+            // time, where XInput2 would flag it: drop the press
+            let key: xlib::XKeyEvent = From::from(*event);
+            unsafe {
+                if xlib::XPending(display) > 0 {
+                    let mut next: xlib::XEvent = zeroed();
+                    xlib::XPeekEvent(display, &mut next);
+                    if next.get_type() == xlib::KeyPress {
+                        let press: xlib::XKeyEvent = From::from(next);
+                        if press.keycode == key.keycode && press.time == key.time {
+                            xlib::XNextEvent(display, &mut next);
+                            return None;
+                        }
+                    }
+                }
             }
-            true
+            None
+        }
+        xlib::ButtonPress | xlib::ButtonRelease => {
+            let b: xlib::XButtonEvent = From::from(*event);
+            let (x, y, time) = (b.x as f64, b.y as f64, b.time);
+            let action = if event.get_type() == xlib::ButtonPress {
+                button_pressed(clock_window, gestures, b.button, x, y, time)
+            } else {
+                button_released(clock_window, gestures, b.button, x, y, time)
+            };
+            clock_window.touched();
+            action
         }
-    });
+        xlib::MotionNotify => {
+            clock_window.touched();
+            None
+        }
+        other => {
+            trace!("event {}", other);
+            None
+        }
+    }
+}
+
+fn key_pressed(clock_window: &ClockWindow, keycode: u32) -> Option<action::Action> {
+    debug!("Key {} pressed", keycode);
+    let sym = unsafe { xlib::XkbKeycodeToKeysym(clock_window.display, keycode as u8, 0, 0) };
+    clock_window.key_action(sym)
+}
+
+fn button_pressed(
+    clock_window: &mut ClockWindow,
+    gestures: &mut gesture::Recogniser,
+    button: u32,
+    x: f64,
+    y: f64,
+    time: u64,
+) -> Option<action::Action> {
+    debug!("Button {} pressed", button);
+    // a touch that wakes the display at night does nothing else
+    if clock_window.wake() {
+        return None;
+    }
+    if button == gesture::BUTTON {
+        gestures.press(x, y, time);
+        return None;
+    }
+    clock_window.button_action(button)
+}
+
+fn button_released(
+    clock_window: &mut ClockWindow,
+    gestures: &mut gesture::Recogniser,
+    button: u32,
+    x: f64,
+    y: f64,
+    time: u64,
+) -> Option<action::Action> {
+    debug!("Button {} released", button);
+    if button != gesture::BUTTON {
+        return None;
+    }
+    let gesture = gestures.release(x, y, time)?;
+    debug!("gesture: {:?}", gesture);
+    // only a touch controls the stopwatch, not "dismiss" from elsewhere
+    if gesture == gesture::Gesture::Tap && clock_window.tap_stopwatch() {
+        return None;
+    }
+    clock_window.gesture_action(gesture)
 }
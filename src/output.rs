@@ -0,0 +1,82 @@
+// output.rs
+
+// find where an XRandR output sits on the screen, so the clock can be
+// put on one particular monitor
+
+use std::ffi::CStr;
+use std::os::raw::c_int;
+use x11::{xlib, xrandr};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Ask for notification when outputs are connected, disconnected or
+/// reconfigured; returns the extension's event base, None without XRandR
+pub fn select_changes(display: *mut xlib::Display, root: xlib::Window) -> Option<c_int> {
+    let mut event_base = 0;
+    let mut error_base = 0;
+    unsafe {
+        if xrandr::XRRQueryExtension(display, &mut event_base, &mut error_base) == xlib::False {
+            return None;
+        }
+        xrandr::XRRSelectInput(
+            display,
+            root,
+            xrandr::RRScreenChangeNotifyMask | xrandr::RROutputChangeNotifyMask,
+        );
+    }
+    Some(event_base)
+}
+
+/// Every output by name, with its geometry if connected and active
+pub fn list(display: *mut xlib::Display, root: xlib::Window) -> Vec<(String, Option<Geometry>)> {
+    let mut outputs = Vec::new();
+    unsafe {
+        let resources = xrandr::XRRGetScreenResourcesCurrent(display, root);
+        if resources.is_null() {
+            return outputs;
+        }
+        let ids = std::slice::from_raw_parts((*resources).outputs, (*resources).noutput as usize);
+        for id in ids {
+            let info = xrandr::XRRGetOutputInfo(display, resources, *id);
+            if info.is_null() {
+                continue;
+            }
+            let name = CStr::from_ptr((*info).name).to_string_lossy().to_string();
+
+            let mut geometry = None;
+            if (*info).connection == xrandr::RR_Connected as u16 && (*info).crtc != 0 {
+                let crtc = xrandr::XRRGetCrtcInfo(display, resources, (*info).crtc);
+                if !crtc.is_null() {
+                    if (*crtc).width > 0 && (*crtc).height > 0 {
+                        geometry = Some(Geometry {
+                            x: (*crtc).x,
+                            y: (*crtc).y,
+                            width: (*crtc).width,
+                            height: (*crtc).height,
+                        });
+                    }
+                    xrandr::XRRFreeCrtcInfo(crtc);
+                }
+            }
+            xrandr::XRRFreeOutputInfo(info);
+
+            outputs.push((name, geometry));
+        }
+        xrandr::XRRFreeScreenResources(resources);
+    }
+    outputs
+}
+
+/// The geometry of the named output, None if it is missing or off
+pub fn find(display: *mut xlib::Display, root: xlib::Window, name: &str) -> Option<Geometry> {
+    list(display, root)
+        .into_iter()
+        .find(|(n, _)| n == name)
+        .and_then(|(_, geometry)| geometry)
+}
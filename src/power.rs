@@ -0,0 +1,156 @@
+// power.rs
+
+// the panel's power state: keep the X screensaver and DPMS from
+// blanking the clock, and switch the display off during scheduled hours
+
+use crate::configure;
+use chrono::prelude::*;
+use log::warn;
+use std::time::{Duration, Instant};
+use x11::{dpms, xlib, xss};
+
+pub struct Power {
+    display: *mut xlib::Display,
+    config: configure::Power,
+    dpms: bool,      // the server has DPMS
+    scheduled: bool, // switched off by the schedule
+    saved: Option<Saved>,
+}
+
+// the DPMS settings from before the schedule switched the display off
+struct Saved {
+    timeouts: (u16, u16, u16), // standby, suspend, off
+    enabled: bool,
+}
+
+impl Power {
+    pub fn new(display: *mut xlib::Display, config: configure::Power) -> Power {
+        let mut event_base = 0;
+        let mut error_base = 0;
+        let dpms = unsafe {
+            dpms::DPMSQueryExtension(display, &mut event_base, &mut error_base) != xlib::False
+                && dpms::DPMSCapable(display) != xlib::False
+        };
+        if !dpms && config.off.is_some() {
+            warn!("no DPMS: the display cannot be switched off");
+        }
+
+        let power = Power {
+            display,
+            config,
+            dpms,
+            scheduled: false,
+            saved: None,
+        };
+        if config.keep_on {
+            power.keep_on();
+        }
+        power
+    }
+
+    // the same as "xset s off s noblank -dpms"; the screensaver
+    // suspension lapses by itself when the clock disconnects
+    fn keep_on(&self) {
+        unsafe {
+            xlib::XSetScreenSaver(
+                self.display,
+                0,
+                0,
+                xlib::DontPreferBlanking,
+                xlib::DefaultExposures,
+            );
+            let mut event_base = 0;
+            let mut error_base = 0;
+            if xss::XScreenSaverQueryExtension(self.display, &mut event_base, &mut error_base)
+                != xlib::False
+            {
+                xss::XScreenSaverSuspend(self.display, xlib::True);
+            }
+            if self.dpms {
+                dpms::DPMSDisable(self.display);
+            }
+        }
+    }
+
+    /// Follow the schedule: off between the off and on times, except
+    /// for |wake| seconds after a touch (which the server itself
+    /// answers by turning the display back on) and while |alerting|
+    pub fn update(&mut self, dt: &DateTime<Local>, last_touch: Instant, alerting: bool) {
+        let (off, on) = match (self.config.off, self.config.on) {
+            (Some(off), Some(on)) if self.dpms => (off, on),
+            _ => return,
+        };
+
+        let now = dt.hour() * 60 + dt.minute();
+        let off = off.0 * 60 + off.1;
+        let on = on.0 * 60 + on.1;
+        let in_window = if off <= on {
+            off <= now && now < on
+        } else {
+            now >= off || now < on
+        };
+
+        let wake = Duration::from_secs(self.config.wake.max(0) as u64);
+        if in_window && !alerting {
+            if last_touch.elapsed() >= wake && !self.is_off() {
+                self.scheduled = true;
+                self.set_off(true);
+            }
+        } else if self.scheduled {
+            self.scheduled = false;
+            self.set_off(false);
+        }
+    }
+
+    fn is_off(&self) -> bool {
+        let mut level = 0;
+        let mut enabled = 0;
+        unsafe {
+            dpms::DPMSInfo(self.display, &mut level, &mut enabled);
+        }
+        enabled != 0 && level != dpms::DPMSModeOn
+    }
+
+    fn set_off(&mut self, off: bool) {
+        unsafe {
+            if off {
+                if self.saved.is_none() {
+                    let mut timeouts = (0, 0, 0);
+                    dpms::DPMSGetTimeouts(
+                        self.display,
+                        &mut timeouts.0,
+                        &mut timeouts.1,
+                        &mut timeouts.2,
+                    );
+                    let mut level = 0;
+                    let mut enabled = 0;
+                    dpms::DPMSInfo(self.display, &mut level, &mut enabled);
+                    self.saved = Some(Saved {
+                        timeouts,
+                        enabled: enabled != 0,
+                    });
+                }
+                // forcing a level needs DPMS enabled, but with no
+                // timeouts it will not blank by itself
+                dpms::DPMSSetTimeouts(self.display, 0, 0, 0);
+                dpms::DPMSEnable(self.display);
+                dpms::DPMSForceLevel(self.display, dpms::DPMSModeOff);
+            } else {
+                dpms::DPMSForceLevel(self.display, dpms::DPMSModeOn);
+                // put back the user's settings
+                let enabled = match self.saved.take() {
+                    Some(saved) => {
+                        let (standby, suspend, off) = saved.timeouts;
+                        dpms::DPMSSetTimeouts(self.display, standby, suspend, off);
+                        saved.enabled
+                    }
+                    None => true,
+                };
+                if self.config.keep_on || !enabled {
+                    dpms::DPMSDisable(self.display);
+                }
+            }
+            xlib::XFlush(self.display);
+        }
+    }
+}
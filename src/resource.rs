@@ -0,0 +1,304 @@
+// resource.rs
+
+// owners for X and Xft resources: each frees its resource when dropped
+// and holds the display open until then, so a reload or rebuilt theme
+// leaves nothing behind on the server
+
+use std::ffi::CString;
+use std::ops::Deref;
+use std::os::raw::{c_int, c_uint};
+use std::ptr::null;
+use std::rc::Rc;
+use x11::{xft, xlib};
+
+pub struct Display {
+    raw: *mut xlib::Display,
+}
+
+impl Display {
+    pub fn open() -> Option<Rc<Display>> {
+        let raw = unsafe { xlib::XOpenDisplay(null()) };
+        if raw.is_null() {
+            None
+        } else {
+            Some(Rc::new(Display { raw }))
+        }
+    }
+
+    pub fn raw(&self) -> *mut xlib::Display {
+        self.raw
+    }
+}
+
+impl Drop for Display {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XCloseDisplay(self.raw);
+        }
+    }
+}
+
+pub struct Font {
+    display: Rc<Display>,
+    raw: *mut xft::XftFont,
+}
+
+impl Font {
+    /// Open a font by fontconfig pattern, None if nothing matches
+    pub fn open(display: &Rc<Display>, screen: c_int, name: &str) -> Option<Font> {
+        let name = CString::new(name).ok()?;
+        let raw = unsafe { xft::XftFontOpenName(display.raw(), screen, name.as_ptr()) };
+        if raw.is_null() {
+            None
+        } else {
+            Some(Font {
+                display: display.clone(),
+                raw,
+            })
+        }
+    }
+
+    pub fn raw(&self) -> *mut xft::XftFont {
+        self.raw
+    }
+}
+
+impl Drop for Font {
+    fn drop(&mut self) {
+        unsafe {
+            xft::XftFontClose(self.display.raw(), self.raw);
+        }
+    }
+}
+
+pub struct Colourmap {
+    display: Rc<Display>,
+    raw: xlib::Colormap,
+    visual: *mut xlib::Visual,
+}
+
+impl Colourmap {
+    pub fn new(
+        display: &Rc<Display>,
+        window: xlib::Window,
+        visual: *mut xlib::Visual,
+    ) -> Rc<Colourmap> {
+        let raw = unsafe { xlib::XCreateColormap(display.raw(), window, visual, xlib::AllocNone) };
+        Rc::new(Colourmap {
+            display: display.clone(),
+            raw,
+            visual,
+        })
+    }
+}
+
+impl Drop for Colourmap {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XFreeColormap(self.display.raw(), self.raw);
+        }
+    }
+}
+
+// derefs to the XftColor for drawing
+pub struct Colour {
+    colourmap: Rc<Colourmap>,
+    colour: xft::XftColor,
+}
+
+impl Colour {
+    /// Allocate a colour by X11 name or #rrggbb, None if unknown
+    pub fn alloc(colourmap: &Rc<Colourmap>, name: &str) -> Option<Colour> {
+        let name = CString::new(name).ok()?;
+        unsafe {
+            let mut colour: xft::XftColor = std::mem::zeroed();
+            let ok = xft::XftColorAllocName(
+                colourmap.display.raw(),
+                colourmap.visual,
+                colourmap.raw,
+                name.as_ptr(),
+                &mut colour,
+            );
+            if ok == 0 {
+                None
+            } else {
+                Some(Colour {
+                    colourmap: colourmap.clone(),
+                    colour,
+                })
+            }
+        }
+    }
+}
+
+impl Deref for Colour {
+    type Target = xft::XftColor;
+
+    fn deref(&self) -> &xft::XftColor {
+        &self.colour
+    }
+}
+
+impl Drop for Colour {
+    fn drop(&mut self) {
+        let map = &self.colourmap;
+        unsafe {
+            xft::XftColorFree(map.display.raw(), map.visual, map.raw, &mut self.colour);
+        }
+    }
+}
+
+pub struct Pixmap {
+    display: Rc<Display>,
+    raw: xlib::Pixmap,
+}
+
+impl Pixmap {
+    pub fn new(
+        display: &Rc<Display>,
+        drawable: xlib::Drawable,
+        width: u32,
+        height: u32,
+        depth: u32,
+    ) -> Pixmap {
+        let raw = unsafe {
+            xlib::XCreatePixmap(
+                display.raw(),
+                drawable,
+                width as c_uint,
+                height as c_uint,
+                depth as c_uint,
+            )
+        };
+        Pixmap {
+            display: display.clone(),
+            raw,
+        }
+    }
+
+    pub fn raw(&self) -> xlib::Pixmap {
+        self.raw
+    }
+}
+
+impl Drop for Pixmap {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XFreePixmap(self.display.raw(), self.raw);
+        }
+    }
+}
+
+pub struct Draw {
+    _colourmap: Rc<Colourmap>, // in use until the draw is destroyed
+    raw: *mut xft::XftDraw,
+}
+
+impl Draw {
+    pub fn new(pixmap: &Pixmap, colourmap: &Rc<Colourmap>) -> Draw {
+        let raw = unsafe {
+            xft::XftDrawCreate(
+                colourmap.display.raw(),
+                pixmap.raw(),
+                colourmap.visual,
+                colourmap.raw,
+            )
+        };
+        Draw {
+            _colourmap: colourmap.clone(),
+            raw,
+        }
+    }
+
+    /// Draw to another pixmap from now on
+    pub fn change(&self, pixmap: &Pixmap) {
+        unsafe {
+            xft::XftDrawChange(self.raw, pixmap.raw());
+        }
+    }
+
+    pub fn raw(&self) -> *mut xft::XftDraw {
+        self.raw
+    }
+}
+
+impl Drop for Draw {
+    fn drop(&mut self) {
+        unsafe {
+            xft::XftDrawDestroy(self.raw);
+        }
+    }
+}
+
+pub struct Gc {
+    display: Rc<Display>,
+    raw: xlib::GC,
+}
+
+impl Gc {
+    pub fn new(display: &Rc<Display>, drawable: xlib::Drawable) -> Gc {
+        let raw = unsafe {
+            let mut values: xlib::XGCValues = std::mem::zeroed();
+            xlib::XCreateGC(display.raw(), drawable, 0, &mut values)
+        };
+        Gc {
+            display: display.clone(),
+            raw,
+        }
+    }
+
+    pub fn raw(&self) -> xlib::GC {
+        self.raw
+    }
+}
+
+impl Drop for Gc {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XFreeGC(self.display.raw(), self.raw);
+        }
+    }
+}
+
+pub struct Cursor {
+    display: Rc<Display>,
+    raw: xlib::Cursor,
+}
+
+impl Cursor {
+    /// An invisible cursor made from an empty one pixel bitmap
+    pub fn blank(display: &Rc<Display>, window: xlib::Window) -> Cursor {
+        let raw = unsafe {
+            let data = [0 as std::os::raw::c_char];
+            let bitmap = xlib::XCreateBitmapFromData(display.raw(), window, data.as_ptr(), 1, 1);
+            let mut colour: xlib::XColor = std::mem::zeroed();
+            let cursor = xlib::XCreatePixmapCursor(
+                display.raw(),
+                bitmap,
+                bitmap,
+                &mut colour,
+                &mut colour,
+                0,
+                0,
+            );
+            xlib::XFreePixmap(display.raw(), bitmap);
+            cursor
+        };
+        Cursor {
+            display: display.clone(),
+            raw,
+        }
+    }
+
+    pub fn raw(&self) -> xlib::Cursor {
+        self.raw
+    }
+}
+
+impl Drop for Cursor {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XFreeCursor(self.display.raw(), self.raw);
+        }
+    }
+}
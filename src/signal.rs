@@ -0,0 +1,76 @@
+// signal.rs
+
+// SIGTERM and SIGINT delivered through a signalfd, so they are seen by
+// the event loop between redraws and the clock can shut down cleanly
+
+use std::io;
+use std::mem::{size_of, zeroed};
+use std::os::unix::io::RawFd;
+use std::ptr::null_mut;
+
+pub struct Signals {
+    fd: RawFd,
+    received: Option<i32>,
+}
+
+impl Signals {
+    /// Block the signals and open a descriptor to read them from; call
+    /// before starting any threads so they all inherit the mask
+    pub fn new() -> io::Result<Signals> {
+        unsafe {
+            let mut mask: libc::sigset_t = zeroed();
+            libc::sigemptyset(&mut mask);
+            libc::sigaddset(&mut mask, libc::SIGTERM);
+            libc::sigaddset(&mut mask, libc::SIGINT);
+            if libc::pthread_sigmask(libc::SIG_BLOCK, &mask, null_mut()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let fd = libc::signalfd(-1, &mask, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Signals { fd, received: None })
+        }
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Consume a pending signal, call when the fd is readable; true if
+    /// one arrived
+    pub fn read(&mut self) -> bool {
+        let mut info: libc::signalfd_siginfo = unsafe { zeroed() };
+        let n = unsafe {
+            libc::read(
+                self.fd,
+                &mut info as *mut libc::signalfd_siginfo as *mut libc::c_void,
+                size_of::<libc::signalfd_siginfo>(),
+            )
+        };
+        if n == size_of::<libc::signalfd_siginfo>() as isize {
+            self.received = Some(info.ssi_signo as i32);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The exit status for the way the clock was stopped: zero unless
+    /// a signal, then 128 plus its number as a shell would report
+    pub fn exit_code(&self) -> i32 {
+        match self.received {
+            Some(signal) => 128 + signal,
+            None => 0,
+        }
+    }
+}
+
+impl Drop for Signals {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
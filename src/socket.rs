@@ -2,119 +2,422 @@
 
 // main.rs
 
+use crate::action::Action;
+use crate::alarm::Alarm;
+use crate::timer::{self, Countdown, Stopwatch};
+use log::{debug, log_enabled, warn, Level};
 use std::fs;
-use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::io::AsRawFd;
 use std::os::unix::net::{UnixListener, UnixStream};
-use std::sync::{Arc, Mutex};
-use std::thread;
 
 pub struct Input {
     pub sync: bool,
     pub weather: String,
     pub temperature: String,
+    pub alarms: Vec<Alarm>, // one-shot alarms set through the socket
+    pub alert: Option<String>,
+    pub countdown: Option<Countdown>,
+    pub stopwatch: Stopwatch,
+    pub actions: Vec<Action>,    // waiting for the display loop
+    pub brightness: Option<u32>, // percent, overrides the theme's
+    pub light: Option<f64>,      // ambient light reading
 }
 
 impl Input {
-    fn new() -> Input {
+    pub fn new() -> Input {
         Input {
             sync: false,
             weather: "----".to_string(),     //String::new(),
             temperature: "----".to_string(), //String::new(),
+            alarms: Vec::new(),
+            alert: None,
+            countdown: None,
+            stopwatch: Stopwatch::default(),
+            actions: Vec::new(),
+            brightness: None,
+            light: None,
+        }
+    }
+
+    /// Clear an alert or a finished countdown, true if there was one
+    pub fn dismiss(&mut self) -> bool {
+        if self.alert.take().is_some() {
+            return true;
+        }
+        match self.countdown {
+            Some(c) if c.is_expired() => {
+                self.countdown = None;
+                true
+            }
+            _ => false,
         }
     }
 }
 
-fn handle_client(stream: UnixStream, input: Arc<Mutex<Input>>, debug: bool) {
-    if debug {
-        println!("thread starting…");
+impl Default for Input {
+    fn default() -> Input {
+        Input::new()
     }
+}
 
-    let mut stream = BufReader::new(stream);
-    loop {
-        let mut buf = String::new();
-        if stream.read_line(&mut buf).is_err() {
-            break;
-        }
-        if buf.len() < 3 || buf == "\r\n" || buf == "\n" {
-            if debug {
-                println!("empty line");
+// word commands: "timer start 5m", "timer stop", "stopwatch start|stop|reset"
+// and the names of actions other than "run"
+fn word_command(f: &mut Input, line: &str) -> bool {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["timer", "start", d] => match timer::parse_duration(d) {
+            Some(duration) => {
+                f.countdown = Some(Countdown::new(duration));
+                true
             }
-            break;
+            None => false,
+        },
+        ["timer", "stop"] => {
+            f.countdown = None;
+            true
         }
+        ["stopwatch", "start"] => {
+            f.stopwatch.start();
+            true
+        }
+        ["stopwatch", "stop"] => {
+            f.stopwatch.stop();
+            true
+        }
+        ["stopwatch", "reset"] => {
+            f.stopwatch.reset();
+            true
+        }
+        _ => match Action::parse(line) {
+            Some(Action::Run(_)) | None => false,
+            Some(action) => {
+                f.actions.push(action);
+                true
+            }
+        },
+    }
+}
 
-        let set = {
-            let mut f = input.lock().unwrap();
-            let b = buf.trim();
-
-            let command = &b[0..2];
-            match command {
-                "s=" => match &b[2..3] {
-                    "1" | "y" | "Y" => {
-                        (*f).sync = true;
-                        true
-                    }
-                    "0" | "n" | "N" => {
-                        (*f).sync = false;
+// apply one line from a client, true if it changed anything
+fn command(f: &mut Input, b: &str) -> bool {
+    // get() rather than slicing, as a short or non-ASCII line has no
+    // two byte prefix
+    let (command, value) = match (b.get(..2), b.get(2..)) {
+        (Some(command), Some(value)) => (command, value),
+        _ => return word_command(f, b),
+    };
+    match command {
+        "s=" => match value.chars().next() {
+            Some('1') | Some('y') | Some('Y') => {
+                f.sync = true;
+                true
+            }
+            Some('0') | Some('n') | Some('N') => {
+                f.sync = false;
+                true
+            }
+            _ => false,
+        },
+        "w=" => {
+            f.weather = value.to_string();
+            true
+        }
+        "t=" => {
+            f.temperature = value.to_string();
+            true
+        }
+        "a=" => match value {
+            "-" => {
+                f.alarms.clear();
+                true
+            }
+            a => {
+                let mut parts = a.splitn(2, ' ');
+                let time = parts.next().unwrap_or("");
+                let label = parts.next().unwrap_or("").trim();
+                match Alarm::new(time, label) {
+                    Some(alarm) => {
+                        f.alarms.push(alarm);
                         true
                     }
-                    _ => false,
-                },
-                "w=" => {
-                    (*f).weather = b[2..].to_string();
-                    true
+                    None => false,
                 }
-                "t=" => {
-                    (*f).temperature = b[2..].to_string();
+            }
+        },
+        "d=" => {
+            f.actions.push(Action::Dismiss);
+            true
+        }
+        "l=" => match value.parse::<f64>() {
+            Ok(lux) if lux >= 0.0 => {
+                f.light = Some(lux);
+                true
+            }
+            _ => false,
+        },
+        "b=" => match value {
+            "-" => {
+                f.brightness = None;
+                true
+            }
+            n => match n.parse::<u32>() {
+                Ok(percent) if percent <= 100 => {
+                    f.brightness = Some(percent);
                     true
                 }
                 _ => false,
+            },
+        },
+        _ => word_command(f, b),
+    }
+}
+
+// the longest partial line kept for a client; one that goes on
+// without a newline is dropped
+const MAX_LINE: usize = 4096;
+
+// a connection and its partial line
+struct Client {
+    stream: UnixStream,
+    buffer: Vec<u8>,
+}
+
+/// Send one command line to the clock listening on |socket|
+pub fn send(socket: &str, line: &str) -> std::io::Result<()> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.write_all(line.as_bytes())?;
+    stream.write_all(b"\n")
+}
+
+// the listening socket and its connections, serviced from the
+// display's poll loop rather than by threads
+pub struct Server {
+    path: String,
+    listener: UnixListener,
+    clients: Vec<Client>,
+}
+
+impl Server {
+    pub fn new(socket: &str) -> std::io::Result<Server> {
+        match fs::remove_file(socket) {
+            Ok(_) => (),
+            Err(e) => {
+                if e.kind() != ErrorKind::NotFound {
+                    return Err(e);
+                }
             }
-        };
-        if debug {
-            if set {
-                stream.get_ref().write("set to: ".as_bytes()).unwrap();
-            }
-            stream.get_ref().write(buf.as_bytes()).unwrap();
         }
+
+        let listener = UnixListener::bind(socket)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Server {
+            path: socket.to_string(),
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Entries to poll: the listener followed by each client
+    pub fn pollfds(&self) -> Vec<libc::pollfd> {
+        std::iter::once(self.listener.as_raw_fd())
+            .chain(self.clients.iter().map(|c| c.stream.as_raw_fd()))
+            .map(|fd| libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect()
     }
-    if debug {
-        println!("thread stopped");
+
+    /// Process the results of polling the entries from pollfds: accept
+    /// new connections and apply complete lines from clients, dropping
+    /// any that have closed; true if the input changed
+    pub fn service(&mut self, polled: &[libc::pollfd], input: &mut Input) -> bool {
+        let mut changed = false;
+
+        // clients first, as accepting changes the indices
+        let mut closed = Vec::new();
+        for (i, p) in polled.iter().enumerate().skip(1) {
+            if p.revents == 0 {
+                continue;
+            }
+            let client = &mut self.clients[i - 1];
+            let (open, set) = Server::read_client(client, input);
+            changed |= set;
+            if !open {
+                closed.push(i - 1);
+            }
+        }
+        for i in closed.into_iter().rev() {
+            debug!("connection closed");
+            self.clients.remove(i);
+        }
+
+        if polled.first().is_some_and(|p| p.revents != 0) {
+            loop {
+                match self.listener.accept() {
+                    Ok((stream, _)) => {
+                        if let Err(err) = stream.set_nonblocking(true) {
+                            warn!("socket connection: {}", err);
+                            continue;
+                        }
+                        debug!("connection accepted");
+                        self.clients.push(Client {
+                            stream,
+                            buffer: Vec::new(),
+                        });
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(err) => {
+                        warn!("socket accept: {}", err);
+                        break;
+                    }
+                }
+            }
+        }
+
+        changed
     }
-}
 
-pub fn setup(socket: &str, debug: bool) -> std::io::Result<Arc<Mutex<Input>>> {
-    match fs::remove_file(socket) {
-        Ok(_) => (),
-        Err(e) => {
-            if e.kind() != ErrorKind::NotFound {
-                return Err(e);
+    // read what is available and apply each complete line; returns
+    // (still open, anything set)
+    fn read_client(client: &mut Client, input: &mut Input) -> (bool, bool) {
+        let mut set = false;
+        let mut eof = false;
+        let mut chunk = [0u8; 512];
+        loop {
+            match client.stream.read(&mut chunk) {
+                Ok(0) => {
+                    eof = true;
+                    break;
+                }
+                Ok(n) => {
+                    client.buffer.extend_from_slice(&chunk[..n]);
+                    // leave the rest until the lines so far are used
+                    if client.buffer.len() > MAX_LINE {
+                        break;
+                    }
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    eof = true;
+                    break;
+                }
             }
         }
+
+        // a final unterminated line still counts at end of input
+        if eof && !client.buffer.is_empty() {
+            client.buffer.push(b'\n');
+        }
+
+        while let Some(end) = client.buffer.iter().position(|&c| c == b'\n') {
+            let line: Vec<u8> = client.buffer.drain(..=end).collect();
+            let buf = String::from_utf8_lossy(&line).to_string();
+
+            if buf.len() < 3 || buf == "\r\n" || buf == "\n" {
+                debug!("empty line");
+                return (false, set);
+            }
+
+            let done = command(input, buf.trim());
+            set |= done;
+            debug!(
+                "command: {} ({})",
+                buf.trim(),
+                if done { "set" } else { "no change" }
+            );
+
+            // echo back for debugging by hand, e.g. with nc
+            if log_enabled!(Level::Debug) {
+                if done {
+                    client.stream.write_all("set to: ".as_bytes()).ok();
+                }
+                client.stream.write_all(buf.as_bytes()).ok();
+            }
+        }
+
+        if client.buffer.len() > MAX_LINE {
+            warn!(
+                "socket line over {} bytes, closing the connection",
+                MAX_LINE
+            );
+            return (false, set);
+        }
+
+        (!eof, set)
     }
+}
+
+impl Drop for Server {
+    /// Removes the socket file
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
 
-    let listener = UnixListener::bind(socket)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
 
-    let flag = Arc::new(Mutex::new(Input::new()));
+    #[test]
+    fn timer() {
+        let mut f = Input::new();
+        assert!(command(&mut f, "timer start 5m"));
+        let remaining = f.countdown.map(|c| c.remaining());
+        assert_eq!(remaining, Some(Duration::from_secs(300)));
+        assert!(command(&mut f, "timer stop"));
+        assert!(f.countdown.is_none());
+    }
 
-    let f = flag.clone();
+    #[test]
+    fn timer_rejected() {
+        let mut f = Input::new();
+        assert!(!command(&mut f, "timer start"));
+        assert!(!command(&mut f, "timer start soon"));
+        assert!(!command(&mut f, "timer start 5m extra"));
+        assert!(!command(&mut f, "timer start 18446744073709551615s"));
+        assert!(!command(&mut f, "timer start 18446744073709551619"));
+        assert!(f.countdown.is_none());
+    }
 
-    thread::spawn(move ||
+    #[test]
+    fn stopwatch() {
+        let mut f = Input::new();
+        assert!(!f.stopwatch.is_active());
+        assert!(command(&mut f, "stopwatch start"));
+        assert!(f.stopwatch.is_active());
+        assert!(command(&mut f, "stopwatch stop"));
+        assert!(command(&mut f, "stopwatch reset"));
+        assert!(!f.stopwatch.is_active());
+        assert!(!command(&mut f, "stopwatch lap"));
+    }
 
-                  // accept connections and process them, spawning a new thread for each one
-                  for stream in listener.incoming() {
-                      match stream {
-                          Ok(stream) => {
-                              let f = flag.clone();
-                              thread::spawn(move || handle_client(stream, f ,debug));
-                          }
-                          Err(err) => {
-                              if debug {
-                                  println!("error: {}", err);
-                              }
-                              break;
-                          }
-                      }
-                  });
+    #[test]
+    fn actions() {
+        let mut f = Input::new();
+        assert!(command(&mut f, "next_page"));
+        assert!(command(&mut f, "d="));
+        assert_eq!(f.actions, vec![Action::NextPage, Action::Dismiss]);
+        // commands are not run from the socket
+        assert!(!command(&mut f, "run reboot"));
+        assert!(!command(&mut f, "frobnicate"));
+        assert_eq!(f.actions.len(), 2);
+    }
 
-    Ok(f)
+    #[test]
+    fn short_and_non_ascii() {
+        let mut f = Input::new();
+        assert!(!command(&mut f, ""));
+        assert!(!command(&mut f, "s"));
+        assert!(!command(&mut f, "é"));
+        assert!(!command(&mut f, "aé"));
+        assert!(!command(&mut f, "s=é"));
+        assert!(command(&mut f, "w=é"));
+        assert_eq!(f.weather, "é");
+    }
 }
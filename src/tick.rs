@@ -0,0 +1,91 @@
+// tick.rs
+
+// a timerfd that becomes readable on every whole second of the wall
+// clock, re-aligning itself whenever the clock is stepped (e.g. by ntpd)
+
+use log::warn;
+use std::io;
+use std::mem::zeroed;
+use std::os::raw::c_int;
+use std::os::unix::io::RawFd;
+use std::ptr::null_mut;
+
+pub struct Ticker {
+    fd: RawFd,
+}
+
+impl Ticker {
+    pub fn new() -> io::Result<Ticker> {
+        let fd = unsafe {
+            libc::timerfd_create(libc::CLOCK_REALTIME, libc::TFD_NONBLOCK | libc::TFD_CLOEXEC)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let ticker = Ticker { fd };
+        ticker.arm()?;
+        Ok(ticker)
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    // first expiry at the next whole second then once a second after
+    // that; the absolute time is cancelled if the clock is set
+    fn arm(&self) -> io::Result<()> {
+        unsafe {
+            let mut now: libc::timespec = zeroed();
+            if libc::clock_gettime(libc::CLOCK_REALTIME, &mut now) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut spec: libc::itimerspec = zeroed();
+            spec.it_value.tv_sec = now.tv_sec + 1;
+            spec.it_value.tv_nsec = 0;
+            spec.it_interval.tv_sec = 1;
+            spec.it_interval.tv_nsec = 0;
+
+            let flags: c_int = libc::TFD_TIMER_ABSTIME | libc::TFD_TIMER_CANCEL_ON_SET;
+            if libc::timerfd_settime(self.fd, flags, &spec, null_mut()) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Consume a pending tick, call when the fd is readable; true if
+    /// the display should be redrawn
+    pub fn read(&self) -> bool {
+        let mut expirations: u64 = 0;
+        let n = unsafe {
+            libc::read(
+                self.fd,
+                &mut expirations as *mut u64 as *mut libc::c_void,
+                std::mem::size_of::<u64>(),
+            )
+        };
+        if n == std::mem::size_of::<u64>() as isize {
+            return true;
+        }
+
+        match io::Error::last_os_error().raw_os_error() {
+            // the clock was stepped: realign to the new second boundary
+            Some(libc::ECANCELED) => {
+                if let Err(e) = self.arm() {
+                    warn!("cannot rearm tick timer: {}", e);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+// timer.rs
+
+use std::time::{Duration, Instant};
+
+// the longest countdown accepted, well short of overflowing an Instant
+const MAX_DURATION: u64 = 100 * 24 * 3600;
+
+// counts down to a fixed instant
+#[derive(Debug, Clone, Copy)]
+pub struct Countdown {
+    end: Instant,
+}
+
+impl Countdown {
+    pub fn new(duration: Duration) -> Countdown {
+        Countdown {
+            end: Instant::now() + duration,
+        }
+    }
+
+    /// time left rounded up to whole seconds, so a fresh five minute
+    /// countdown shows 5:00 rather than 4:59
+    pub fn remaining(&self) -> Duration {
+        let left = self.end.saturating_duration_since(Instant::now());
+        let seconds = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+        Duration::from_secs(seconds)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.end
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stopwatch {
+    started: Option<Instant>,
+    elapsed: Duration, // accumulated before the current start
+}
+
+impl Stopwatch {
+    pub fn start(&mut self) {
+        if self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(started) = self.started.take() {
+            self.elapsed += started.elapsed();
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        if self.started.is_some() {
+            self.stop();
+        } else {
+            self.start();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Stopwatch::default();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match self.started {
+            Some(started) => self.elapsed + started.elapsed(),
+            None => self.elapsed,
+        }
+    }
+
+    /// true while running or holding a stopped reading
+    pub fn is_active(&self) -> bool {
+        self.started.is_some() || self.elapsed > Duration::from_secs(0)
+    }
+}
+
+/// Parse durations like "90", "90s", "5m", "1h30m"; a bare number is
+/// seconds, and None for anything empty, zero or over 100 days
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let mut total = 0u64;
+    let mut number: Option<u64> = None;
+    for c in text.trim().chars() {
+        match c {
+            '0'..='9' => {
+                let digit = c as u64 - '0' as u64;
+                number = Some(number.unwrap_or(0).checked_mul(10)?.checked_add(digit)?);
+            }
+            'h' | 'm' | 's' => {
+                let scale = match c {
+                    'h' => 3600,
+                    'm' => 60,
+                    _ => 1,
+                };
+                total = total.checked_add(number.take()?.checked_mul(scale)?)?;
+            }
+            _ => return None,
+        }
+    }
+    if let Some(n) = number {
+        total = total.checked_add(n)?;
+    }
+    if total == 0 || total > MAX_DURATION {
+        None
+    } else {
+        Some(Duration::from_secs(total))
+    }
+}
+
+/// "M:SS" below an hour, "H:MM:SS" above
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < 3600 {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(text: &str) -> Option<u64> {
+        parse_duration(text).map(|d| d.as_secs())
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(seconds("90"), Some(90));
+        assert_eq!(seconds("90s"), Some(90));
+        assert_eq!(seconds("5m"), Some(300));
+        assert_eq!(seconds("2h"), Some(7200));
+        assert_eq!(seconds(" 5m\n"), Some(300));
+    }
+
+    #[test]
+    fn combined_units() {
+        assert_eq!(seconds("1h30m"), Some(5400));
+        assert_eq!(seconds("1m30s"), Some(90));
+        assert_eq!(seconds("1m30"), Some(90)); // trailing seconds
+        assert_eq!(seconds("1h1m1s"), Some(3661));
+    }
+
+    #[test]
+    fn empty_and_garbage() {
+        assert_eq!(seconds(""), None);
+        assert_eq!(seconds("0"), None);
+        assert_eq!(seconds("0m0s"), None);
+        assert_eq!(seconds("m"), None); // a unit without a number
+        assert_eq!(seconds("5mm"), None);
+        assert_eq!(seconds("5x"), None);
+        assert_eq!(seconds("-5"), None);
+        assert_eq!(seconds("1.5m"), None);
+        assert_eq!(seconds("five"), None);
+    }
+
+    #[test]
+    fn overflow() {
+        assert_eq!(seconds("18446744073709551615s"), None);
+        assert_eq!(seconds("18446744073709551616"), None);
+        assert_eq!(seconds("18446744073709551619"), None);
+        assert_eq!(seconds("99999999999999999999h"), None);
+        assert_eq!(seconds("5124095576030431h"), None);
+    }
+
+    #[test]
+    fn longest() {
+        assert_eq!(seconds("2400h"), Some(MAX_DURATION));
+        assert_eq!(seconds("8640000"), Some(MAX_DURATION));
+        assert_eq!(seconds("8640001"), None);
+        assert_eq!(seconds("2400h1s"), None);
+    }
+
+    #[test]
+    fn format() {
+        let format = |s| format_duration(Duration::from_secs(s));
+        assert_eq!(format(0), "0:00");
+        assert_eq!(format(59), "0:59");
+        assert_eq!(format(61), "1:01");
+        assert_eq!(format(3599), "59:59");
+        assert_eq!(format(3600), "1:00:00");
+        assert_eq!(format(3661), "1:01:01");
+        assert_eq!(format(MAX_DURATION), "2400:00:00");
+    }
+}